@@ -1,4 +1,7 @@
-use std::fs;
+use crate::parse::{ParseError, Token};
+use itertools::Itertools;
+use std::fmt::{Display, Formatter, Write};
+use std::{env, fs};
 
 enum Direction {
     Left,
@@ -6,12 +9,11 @@ enum Direction {
 }
 
 impl Direction {
-    fn new(line: &str) -> Direction {
-        let first = line.chars().nth(0).unwrap();
-        match first {
-            'L' => Direction::Left,
-            'R' => Direction::Right,
-            _ => panic!("Invalid direction"),
+    fn new(token: Token) -> Result<Direction, ParseError> {
+        match token.text() {
+            "L" => Ok(Direction::Left),
+            "R" => Ok(Direction::Right),
+            _ => Err(token.error("invalid direction")),
         }
     }
 }
@@ -22,10 +24,23 @@ struct Spin {
 }
 
 impl Spin {
-    fn new(line: &str) -> Self {
-        let direction = Direction::new(line);
-        let amount = line[1..].parse::<i64>().unwrap();
-        Self { direction, amount }
+    fn new(line: Token) -> Result<Self, ParseError> {
+        let (direction, amount) = line
+            .split_at(1)
+            .ok_or_else(|| line.error("invalid direction"))?;
+        let direction = Direction::new(direction)?;
+        let amount = amount.parse()?;
+        Ok(Self { direction, amount })
+    }
+}
+
+impl Display for Spin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let direction = match self.direction {
+            Direction::Left => 'L',
+            Direction::Right => 'R',
+        };
+        write!(f, "{}{}", direction, self.amount)
     }
 }
 
@@ -34,29 +49,63 @@ struct Sequence {
 }
 
 impl Sequence {
-    fn new(content: &str) -> Self {
-        Sequence {
-            spins: content.lines().map(|line| Spin::new(line)).collect(),
-        }
+    fn new(content: &str) -> Result<Self, ParseError> {
+        Ok(Sequence {
+            spins: Token::new(content).lines().map(Spin::new).try_collect()?,
+        })
     }
 }
 
+#[derive(Clone)]
 struct Safe {
     current: i64,
-    limit: i64,
+    size: i64,
+    target: i64,
 }
 
 impl Safe {
-    fn count_zeros(&mut self, sequence: &Sequence) -> usize {
+    fn new(args: &[String]) -> Result<Safe, String> {
+        let mut safe = Safe {
+            current: 50,
+            size: 100,
+            target: 0,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or(format!("{} needs a value", arg))?;
+            let value = value
+                .parse::<i64>()
+                .map_err(|_| format!("invalid value {:?} for {}", value, arg))?;
+            match arg.as_str() {
+                "--size" => safe.size = value,
+                "--start" => safe.current = value,
+                "--target" => safe.target = value,
+                _ => return Err(format!("invalid option {}", arg)),
+            }
+        }
+        if safe.size <= 0 {
+            return Err("size must be positive".to_string());
+        }
+        if !(0..safe.size).contains(&safe.current) || !(0..safe.size).contains(&safe.target) {
+            return Err(format!(
+                "start and target must be positions on a dial of size {}",
+                safe.size
+            ));
+        }
+        Ok(safe)
+    }
+
+    fn count_hits(&mut self, sequence: &Sequence) -> usize {
+        let target = self.target;
         sequence
             .spins
             .iter()
             .map(|spin| self.apply_spin(spin))
-            .filter(|(pos, _)| pos == &0)
+            .filter(|(pos, _)| pos == &target)
             .count()
     }
 
-    fn count_zero_passes(&mut self, sequence: &Sequence) -> usize {
+    fn count_passes(&mut self, sequence: &Sequence) -> usize {
         sequence
             .spins
             .iter()
@@ -65,41 +114,156 @@ impl Safe {
             .sum()
     }
 
+    fn trace(&mut self, sequence: &Sequence) -> Vec<SpinTrace> {
+        sequence
+            .spins
+            .iter()
+            .map(|spin| {
+                let start = self.current;
+                let (end, passes) = self.apply_spin(spin);
+                SpinTrace { start, end, passes }
+            })
+            .collect()
+    }
+
     fn apply_spin(&mut self, spin: &Spin) -> (i64, usize) {
         let delta = match spin.direction {
             Direction::Left => -spin.amount,
             Direction::Right => spin.amount,
         };
-        let position = self.current + delta;
-        let dropped = if self.current != 0 {
+        // count on a dial rotated so that the target sits at zero
+        let relative = (self.current - self.target).rem_euclid(self.size);
+        let position = relative + delta;
+        let dropped = if relative != 0 {
             (position <= 0) as usize
         } else {
             0
         };
-        let flips = (position / (self.limit + 1)).abs() as usize + dropped;
-        self.current = position.rem_euclid(self.limit + 1);
+        let flips = (position / self.size).abs() as usize + dropped;
+        self.current = (position + self.target).rem_euclid(self.size);
+        (self.current, flips)
+    }
+
+    #[cfg(test)]
+    fn apply_spin_stepwise(&mut self, spin: &Spin) -> (i64, usize) {
+        // reference implementation, moves one click at a time
+        let step = match spin.direction {
+            Direction::Left => -1,
+            Direction::Right => 1,
+        };
+        let mut flips = 0;
+        for _ in 0..spin.amount {
+            self.current = (self.current + step).rem_euclid(self.size);
+            if self.current == self.target {
+                flips += 1;
+            }
+        }
         (self.current, flips)
     }
 }
 
-fn part1(sequence: &Sequence) -> usize {
-    let mut safe = Safe {
-        current: 50,
-        limit: 99,
-    };
-    safe.count_zeros(sequence)
+struct SpinTrace {
+    start: i64,
+    end: i64,
+    passes: usize,
 }
 
-fn part2(sequence: &Sequence) -> usize {
-    let mut safe = Safe {
-        current: 50,
-        limit: 99,
-    };
-    safe.count_zero_passes(sequence)
+fn trace_csv(sequence: &Sequence, safe: &Safe) -> String {
+    let mut csv = String::from("spin,start,end,hit,passes\n");
+    let traces = safe.clone().trace(sequence);
+    for (spin, trace) in sequence.spins.iter().zip(traces) {
+        let hit = trace.end == safe.target;
+        writeln!(
+            csv,
+            "{},{},{},{},{}",
+            spin, trace.start, trace.end, hit, trace.passes
+        )
+        .unwrap();
+    }
+    csv
+}
+
+fn part1(sequence: &Sequence, safe: &Safe) -> usize {
+    safe.clone().count_hits(sequence)
+}
+
+fn part2(sequence: &Sequence, safe: &Safe) -> usize {
+    safe.clone().count_passes(sequence)
 }
 pub(crate) fn solve() {
     let contents = fs::read_to_string("1.txt").unwrap();
-    let sequence = Sequence::new(&contents);
-    println!("{}", part1(&sequence));
-    println!("{}", part2(&sequence));
+    let sequence = match Sequence::new(&contents) {
+        Ok(sequence) => sequence,
+        Err(error) => return eprintln!("{}", error),
+    };
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let trace_path = match args.iter().position(|arg| arg == "--trace") {
+        Some(index) if index + 1 < args.len() => args.drain(index..index + 2).nth(1),
+        Some(_) => return eprintln!("--trace needs a path"),
+        None => None,
+    };
+    let safe = match Safe::new(&args) {
+        Ok(safe) => safe,
+        Err(error) => return eprintln!("{}", error),
+    };
+    println!("{}", part1(&sequence, &safe));
+    println!("{}", part2(&sequence, &safe));
+    if let Some(path) = trace_path {
+        fs::write(path, trace_csv(&sequence, &safe)).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn check_spins_agree(rounds: usize, seed: u64) {
+        let mut random = Random::new(seed);
+        let mut next = |limit: u64| random.below(limit);
+        for _ in 0..rounds {
+            let size = 1 + next(200) as i64;
+            let mut fast = Safe {
+                current: next(size as u64) as i64,
+                size,
+                target: next(size as u64) as i64,
+            };
+            let mut reference = fast.clone();
+            for _ in 0..next(20) {
+                let direction = if next(2) == 0 {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                };
+                let amount = match next(8) {
+                    // land exactly on the target, possibly after a few full turns
+                    0 => {
+                        let distance = match direction {
+                            Direction::Left => fast.current - fast.target,
+                            Direction::Right => fast.target - fast.current,
+                        };
+                        distance.rem_euclid(size) + size * next(3) as i64
+                    }
+                    1 => next(100_000) as i64,
+                    _ => next(3 * size as u64) as i64,
+                };
+                let spin = Spin { direction, amount };
+                let start = fast.current;
+                assert_eq!(
+                    fast.apply_spin(&spin),
+                    reference.apply_spin_stepwise(&spin),
+                    "{} from {} on a dial of size {} with target {}",
+                    spin,
+                    start,
+                    size,
+                    fast.target
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn closed_form_and_stepwise_spins_agree() {
+        check_spins_agree(10_000, 0x9E3779B97F4A7C15);
+    }
 }
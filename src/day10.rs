@@ -1,6 +1,6 @@
+use crate::parse::{ParseError, Token};
 use itertools::Itertools;
 use std::fs;
-use std::str::FromStr;
 
 struct Switchboard {
     target: Vec<usize>,
@@ -9,42 +9,30 @@ struct Switchboard {
 }
 
 impl Switchboard {
-    fn new(line: &str) -> Self {
-        let (t, rest) = line.split(']').collect_tuple().unwrap();
+    fn new(line: Token) -> Result<Self, ParseError> {
+        let (t, rest) = line.split_once("]")?;
+        let t = t.strip_prefix("[")?;
         let target = t
-            .chars()
-            .skip(1)
-            .map(|c| match (c) {
-                '.' => 0,
-                '#' => 1,
-                _ => panic!(),
+            .text()
+            .char_indices()
+            .map(|(offset, c)| match (c) {
+                '.' => Ok(0),
+                '#' => Ok(1),
+                _ => Err(t.at(offset).error("invalid light")),
             })
-            .collect();
-        let (s, j) = rest.split("{").collect_tuple().unwrap();
+            .try_collect()?;
+        let (s, j) = rest.split_once("{")?;
         let switches = s
             .trim()
             .split(" ")
-            .map(|switch| {
-                switch
-                    .replace("(", "")
-                    .replace(")", "")
-                    .split(",")
-                    .map(usize::from_str)
-                    .map(Result::unwrap)
-                    .collect()
-            })
-            .collect();
-        let joltages = j
-            .replace("}", "")
-            .split(",")
-            .map(usize::from_str)
-            .map(Result::unwrap)
-            .collect();
-        Self {
+            .map(|switch| switch.strip_prefix("(")?.strip_suffix(")")?.list(","))
+            .try_collect()?;
+        let joltages = j.strip_suffix("}")?.list(",")?;
+        Ok(Self {
             target,
             switches,
             joltages,
-        }
+        })
     }
 
     fn check(&self, switches: &Vec<usize>) -> bool {
@@ -82,9 +70,13 @@ fn part1(switchboards: &Vec<Switchboard>) -> usize {
 
 pub(crate) fn solve() {
     let contents = fs::read_to_string("10.txt").unwrap();
-    let switchboards = contents
+    let switchboards = match Token::new(&contents)
         .lines()
-        .map(|line| Switchboard::new(line))
-        .collect();
+        .map(Switchboard::new)
+        .try_collect()
+    {
+        Ok(switchboards) => switchboards,
+        Err(error) => return eprintln!("{}", error),
+    };
     println!("{}", part1(&switchboards));
 }
@@ -1,55 +1,142 @@
+use crate::parse::{ParseError, Token};
 use itertools::Itertools;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
 use std::collections::{HashMap, HashSet};
-use std::fs;
+use std::fmt::{Display, Write};
+use std::iter::Sum;
+use std::ops::{Add, Mul};
+use std::{env, fs};
+
+trait PathCount: Clone + Add<Output = Self> + Mul<Output = Self> + Sum {
+    fn zero() -> Self;
+    fn one() -> Self;
+}
+
+impl PathCount for usize {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+}
+
+impl PathCount for u128 {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+}
+
+impl PathCount for BigUint {
+    fn zero() -> Self {
+        BigUint::ZERO
+    }
+
+    fn one() -> Self {
+        BigUint::from(1u8)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct ModP<const P: u64>(u64);
+
+impl<const P: u64> Add for ModP<P> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        ModP(((self.0 as u128 + other.0 as u128) % P as u128) as u64)
+    }
+}
+
+impl<const P: u64> Mul for ModP<P> {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        ModP(((self.0 as u128 * other.0 as u128) % P as u128) as u64)
+    }
+}
+
+impl<const P: u64> Sum for ModP<P> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(ModP::zero(), Add::add)
+    }
+}
+
+impl<const P: u64> PathCount for ModP<P> {
+    fn zero() -> Self {
+        ModP(0)
+    }
+
+    fn one() -> Self {
+        ModP(1 % P)
+    }
+}
+
+impl<const P: u64> Display for ModP<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (mod {})", self.0, P)
+    }
+}
 
 struct Graph {
     connections: HashMap<String, Vec<String>>,
 }
 
 impl Graph {
-    fn new(data: &str) -> Self {
+    fn new(data: &str) -> Result<Self, ParseError> {
         let mut connections = HashMap::new();
-        for line in data.lines() {
-            let (node, connected) = line.split(": ").collect_tuple().unwrap();
+        for line in Token::new(data).lines() {
+            let (node, connected) = line.split_once(": ")?;
             connections.insert(
-                node.to_string(),
-                connected.split(" ").map(str::to_string).collect(),
+                node.text().to_string(),
+                connected.split(" ").map(|n| n.text().to_string()).collect(),
             );
         }
 
-        Self { connections }
+        Ok(Self { connections })
     }
 
-    fn count_paths(&self, start: &str, end: &str) -> usize {
+    fn count_paths<T: PathCount>(&self, start: &str, end: &str) -> T {
         let mut known = HashMap::new();
         self.rec_count_paths(&start, &end, &mut known)
     }
 
-    fn rec_count_paths(
+    fn rec_count_paths<T: PathCount>(
         &self,
         current: &str,
         end: &str,
-        known: &mut HashMap<String, usize>,
-    ) -> usize {
+        known: &mut HashMap<String, T>,
+    ) -> T {
         if current == end {
-            known.insert(current.to_string(), 1);
-            1
+            known.insert(current.to_string(), T::one());
+            T::one()
         } else if known.contains_key(current) {
-            known[current]
+            known[current].clone()
         } else {
-            let result = self
+            let result: T = self
                 .connections
                 .get(current)
                 .unwrap_or(&vec![])
                 .iter()
                 .map(|node| self.rec_count_paths(&node, &end, known))
                 .sum();
-            known.insert(current.to_string(), result);
+            known.insert(current.to_string(), result.clone());
             result
         }
     }
 
-    fn count_paths_including(&self, start: &str, end: &str, need_to_see: &HashSet<&str>) -> usize {
+    fn count_paths_including<T: PathCount>(
+        &self,
+        start: &str,
+        end: &str,
+        need_to_see: &HashSet<&str>,
+    ) -> T {
         let mut known_simple = HashMap::new();
         let mut known_complex = HashMap::new();
         let result = self.rec_count_paths_including(
@@ -63,32 +150,32 @@ impl Graph {
         result
     }
 
-    fn rec_count_paths_including(
+    fn rec_count_paths_including<T: PathCount>(
         &self,
         current: &str,
         end: &str,
         need_to_see: &HashSet<&str>,
         seen_so_far: &Vec<String>,
-        known_simple: &mut HashMap<String, usize>,
-        known_complex: &mut HashMap<(String, Vec<String>), usize>,
-    ) -> usize {
+        known_simple: &mut HashMap<String, T>,
+        known_complex: &mut HashMap<(String, Vec<String>), T>,
+    ) -> T {
         let how_many_seen = seen_so_far.len();
         let key = &(current.to_string(), seen_so_far.clone());
         if known_complex.contains_key(key) {
             // we already know how many paths from current lead to a proper solution
-            known_complex[key]
+            known_complex[key].clone()
         } else if how_many_seen == need_to_see.len() {
             // all needed nodes seen, just count all the path till the end
             self.rec_count_paths(current, end, known_simple)
         } else if current == end {
             // end reached but not all nodes seen, bad path
-            0
+            T::zero()
         } else {
             let mut current_seen = seen_so_far.clone();
             if need_to_see.contains(current) {
                 current_seen.push(current.to_string());
             }
-            let result = self
+            let result: T = self
                 .connections
                 .get(current)
                 .unwrap_or(&vec![])
@@ -104,10 +191,163 @@ impl Graph {
                     )
                 })
                 .sum();
-            known_complex.insert((current.to_string(), current_seen), result);
+            known_complex.insert((current.to_string(), current_seen), result.clone());
             result
         }
     }
+
+    fn paths_to<T: PathCount>(&self, end: &str) -> HashMap<String, T> {
+        let mut known = HashMap::new();
+        for node in self.nodes() {
+            self.rec_count_paths(&node, end, &mut known);
+        }
+        known
+    }
+
+    fn paths_from<T: PathCount>(&self, starts: &HashSet<String>) -> HashMap<String, T> {
+        let reversed = self.reversed();
+        let mut known = HashMap::new();
+        for node in self.nodes() {
+            Self::rec_paths_from(&reversed, &node, starts, &mut known);
+        }
+        known
+    }
+
+    fn rec_paths_from<T: PathCount>(
+        reversed: &HashMap<String, Vec<String>>,
+        current: &str,
+        starts: &HashSet<String>,
+        known: &mut HashMap<String, T>,
+    ) -> T {
+        if let Some(count) = known.get(current) {
+            return count.clone();
+        }
+        let own = if starts.contains(current) {
+            T::one()
+        } else {
+            T::zero()
+        };
+        let result: T = own
+            + reversed
+                .get(current)
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|node| Self::rec_paths_from(reversed, node, starts, known))
+                .sum();
+        known.insert(current.to_string(), result.clone());
+        result
+    }
+
+    fn nodes(&self) -> HashSet<String> {
+        self.connections
+            .iter()
+            .flat_map(|(node, connected)| connected.iter().chain([node]))
+            .cloned()
+            .collect()
+    }
+
+    fn reachable(connections: &HashMap<String, Vec<String>>, start: &str) -> HashSet<String> {
+        let mut seen = HashSet::from([start.to_string()]);
+        let mut to_visit = vec![start.to_string()];
+        while let Some(current) = to_visit.pop() {
+            for node in connections.get(&current).unwrap_or(&vec![]) {
+                if seen.insert(node.clone()) {
+                    to_visit.push(node.clone());
+                }
+            }
+        }
+        seen
+    }
+
+    fn reversed(&self) -> HashMap<String, Vec<String>> {
+        let mut reversed: HashMap<String, Vec<String>> = HashMap::new();
+        for (node, connected) in &self.connections {
+            for target in connected {
+                reversed
+                    .entry(target.clone())
+                    .or_default()
+                    .push(node.clone());
+            }
+        }
+        reversed
+    }
+
+    fn subgraph_between(&self, start: &str, end: &str) -> Graph {
+        let forward = Self::reachable(&self.connections, start);
+        let backward = Self::reachable(&self.reversed(), end);
+        let keep: HashSet<&String> = forward.intersection(&backward).collect();
+        let connections = self
+            .connections
+            .iter()
+            .filter(|(node, _)| keep.contains(node))
+            .map(|(node, connected)| {
+                let kept = connected
+                    .iter()
+                    .filter(|target| keep.contains(target))
+                    .cloned()
+                    .collect();
+                (node.clone(), kept)
+            })
+            .collect();
+        Graph { connections }
+    }
+
+    fn to_dot(&self, start: Option<&str>, end: &str) -> String {
+        // without a start, paths may begin at any node without incoming edges
+        let starts = match start {
+            Some(start) => HashSet::from([start.to_string()]),
+            None => {
+                let reversed = self.reversed();
+                self.nodes()
+                    .into_iter()
+                    .filter(|node| !reversed.contains_key(node))
+                    .collect()
+            }
+        };
+        let paths = self.paths_to::<BigUint>(end);
+        let paths_from = self.paths_from::<BigUint>(&starts);
+        let flows: HashMap<(&String, &String), BigUint> = self
+            .connections
+            .iter()
+            .flat_map(|(node, connected)| connected.iter().map(move |target| (node, target)))
+            .map(|(node, target)| ((node, target), &paths_from[node] * &paths[target]))
+            .collect();
+        let max_flow = flows.values().max().cloned().unwrap_or_default();
+        let mut dot = String::from("digraph devices {\n");
+        for node in self.nodes().iter().sorted() {
+            writeln!(
+                dot,
+                "  \"{}\" [label=\"{}\\n{}\"];",
+                node, node, paths[node]
+            )
+            .unwrap();
+        }
+        for (node, connected) in self.connections.iter().sorted() {
+            for target in connected {
+                // flow through an edge is the number of paths from a start to the end using it
+                let flow = &flows[&(node, target)];
+                writeln!(
+                    dot,
+                    "  \"{}\" -> \"{}\" [color=\"0.000 {:.3} 0.900\", label=\"{}\"];",
+                    node,
+                    target,
+                    Self::saturation(flow, &max_flow),
+                    flow
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn saturation(flow: &BigUint, max_flow: &BigUint) -> f64 {
+        // drop low bits first so that counts beyond the range of f64 still give a ratio
+        let shift = max_flow.bits().saturating_sub(f64::MANTISSA_DIGITS as u64);
+        let flow = (flow >> shift).to_f64().unwrap();
+        let max_flow = (max_flow >> shift).to_f64().unwrap().max(1.0);
+        flow / max_flow
+    }
 }
 fn part1(graph: &Graph) -> usize {
     graph.count_paths("you", "out")
@@ -117,19 +357,32 @@ fn part2(graph: &Graph) -> usize {
     graph.count_paths_including("svr", "out", &HashSet::from(["dac", "fft"]))
 }
 
-fn part2_smart(graph: &Graph) -> usize {
-    graph.count_paths("svr", "dac")
+fn part2_smart<T: PathCount>(graph: &Graph) -> T {
+    graph.count_paths::<T>("svr", "dac")
         * graph.count_paths("dac", "fft")
         * graph.count_paths("fft", "out")
-        + graph.count_paths("svr", "fft")
+        + graph.count_paths::<T>("svr", "fft")
             * graph.count_paths("fft", "dac")
             * graph.count_paths("dac", "out")
 }
 
 pub(crate) fn solve() {
     let contents = fs::read_to_string("11.txt").unwrap();
-    let graph = Graph::new(&contents);
+    let graph = match Graph::new(&contents) {
+        Ok(graph) => graph,
+        Err(error) => return eprintln!("{}", error),
+    };
     println!("{}", part1(&graph));
     println!("{}", part2(&graph));
-    println!("{}", part2_smart(&graph));
+    println!("{}", part2_smart::<BigUint>(&graph));
+    println!("{}", part2_smart::<ModP<1_000_000_007>>(&graph));
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect_vec()[..] {
+        ["--dot", path] => fs::write(path, graph.to_dot(None, "out")).unwrap(),
+        ["--dot", path, start, end] => {
+            let subgraph = graph.subgraph_between(start, end);
+            fs::write(path, subgraph.to_dot(Some(start), end)).unwrap()
+        }
+        _ => {}
+    }
 }
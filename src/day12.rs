@@ -1,3 +1,5 @@
+use crate::grid::Grid;
+use crate::parse::{ParseError, Token};
 use itertools::Itertools;
 use std::fs;
 struct Present {
@@ -5,10 +7,20 @@ struct Present {
 }
 
 impl Present {
-    fn new(data: &str) -> Present {
-        Present {
-            size: data.chars().filter(|c| *c == '#').count(),
-        }
+    fn new(data: Token) -> Result<Present, ParseError> {
+        // the first line is the index of the present
+        let (_, shape) = data.split_once(":\n")?;
+        let shape = Grid::parse(shape, false, |c| match c {
+            '#' => Some(true),
+            '.' => Some(false),
+            _ => None,
+        })?;
+        Ok(Present {
+            size: shape
+                .positions()
+                .filter(|&position| shape[position])
+                .count(),
+        })
     }
 }
 
@@ -19,18 +31,11 @@ struct Region {
 }
 
 impl Region {
-    fn new(line: &str) -> Region {
-        let (dim, selected) = line.split(": ").collect_tuple().unwrap();
-        let (x, y) = dim
-            .split("x")
-            .map(|value| value.parse::<usize>().unwrap())
-            .collect_tuple()
-            .unwrap();
-        let presents = selected
-            .split(" ")
-            .map(|value| value.parse::<usize>().unwrap())
-            .collect();
-        Region { x, y, presents }
+    fn new(line: Token) -> Result<Region, ParseError> {
+        let (dim, selected) = line.split_once(": ")?;
+        let (x, y) = dim.pair("x")?;
+        let presents = selected.list(" ")?;
+        Ok(Region { x, y, presents })
     }
 
     fn area(&self) -> usize {
@@ -60,16 +65,14 @@ struct Board {
 }
 
 impl Board {
-    fn new(data: &str) -> Board {
-        let presents = data.split("\n\n").map(|data| Present::new(data)).collect();
-        let regions = data
-            .split("\n\n")
-            .last()
-            .unwrap()
-            .split("\n")
-            .map(|line| Region::new(line))
-            .collect();
-        Board { presents, regions }
+    fn new(data: &str) -> Result<Board, ParseError> {
+        let blocks = Token::new(data).blocks().collect_vec();
+        let (regions, presents) = blocks
+            .split_last()
+            .ok_or_else(|| Token::new(data).error("empty input"))?;
+        let presents = presents.iter().copied().map(Present::new).try_collect()?;
+        let regions = regions.lines().map(Region::new).try_collect()?;
+        Ok(Board { presents, regions })
     }
 
     fn count_valid(&self) -> usize {
@@ -86,6 +89,9 @@ fn part1(board: &Board) -> usize {
 
 pub(crate) fn solve() {
     let contents = fs::read_to_string("12.txt").unwrap();
-    let board = Board::new(&contents);
+    let board = match Board::new(&contents) {
+        Ok(board) => board,
+        Err(error) => return eprintln!("{}", error),
+    };
     println!("{}", part1(&board));
 }
@@ -1,5 +1,9 @@
+use crate::parse::{ParseError, Token};
 use itertools::Itertools;
-use std::fs;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::cmp::{max, min};
+use std::{env, fs};
 
 struct Range {
     start: usize,
@@ -7,55 +11,197 @@ struct Range {
 }
 
 impl Range {
-    fn new(data: &str) -> Range {
-        let (start, end) = data
-            .split("-")
-            .map(str::parse)
-            .map(Result::unwrap)
-            .collect_tuple()
-            .unwrap();
-        Range { start, end }
-    }
-
-    fn count_invalid(&self) -> usize {
-        (self.start..=self.end)
-            .filter(|x| Self::is_invalid(&x.to_string(), 2))
+    fn new(data: Token) -> Result<Range, ParseError> {
+        let (start, end) = data.trim().pair("-")?;
+        Ok(Range { start, end })
+    }
+
+    fn count_invalid(&self) -> u128 {
+        self.digit_lengths()
+            .filter(|digits| digits.is_multiple_of(2))
+            .map(|digits| self.sum_repeated(digits / 2, 2))
             .sum()
     }
 
-    fn count_all_invalid(&self) -> usize {
-        (self.start..=self.end)
-            .filter(|id| Self::is_invalid_any_length(&id.to_string()))
+    fn count_all_invalid(&self) -> u128 {
+        self.digit_lengths()
+            .map(|digits| {
+                // a number made of a repeated block is also made of a block repeated a prime number of times,
+                // numbers repeating with several primes are counted via inclusion-exclusion
+                Self::prime_factors(digits)
+                    .into_iter()
+                    .powerset()
+                    .skip(1)
+                    .map(|primes| {
+                        let repeats: u32 = primes.iter().product();
+                        let sum = self.sum_repeated(digits / repeats, repeats) as i128;
+                        if primes.len() % 2 == 1 { sum } else { -sum }
+                    })
+                    .sum::<i128>() as u128
+            })
             .sum()
     }
 
-    fn is_invalid_any_length(id: &String) -> bool {
-        (2..=id.len()).any(|repeat| Self::is_invalid(id, repeat))
+    fn digit_lengths(&self) -> std::ops::RangeInclusive<u32> {
+        Self::digit_count(self.start)..=Self::digit_count(self.end)
+    }
+
+    fn digit_count(value: usize) -> u32 {
+        value.checked_ilog10().unwrap_or(0) + 1
     }
 
-    fn is_invalid(id: &String, repeats: usize) -> bool {
-        if id.len() % repeats != 0 {
-            false
+    fn prime_factors(value: u32) -> Vec<u32> {
+        (2..=value)
+            .filter(|&p| value.is_multiple_of(p))
+            .filter(|&p| (2..p).all(|d| !p.is_multiple_of(d)))
+            .collect()
+    }
+
+    fn sum_repeated(&self, block_length: u32, repeats: u32) -> u128 {
+        // every such number is block * 1..0001..0001, with the block having no leading zero
+        let multiplier: u128 = (0..repeats).map(|i| 10u128.pow(block_length * i)).sum();
+        let first = max(
+            10u128.pow(block_length - 1),
+            (self.start as u128).div_ceil(multiplier),
+        );
+        let last = min(10u128.pow(block_length) - 1, self.end as u128 / multiplier);
+        if first > last {
+            0
         } else {
-            id.chars()
-                .chunks(id.len() / repeats)
-                .into_iter()
-                .map(|chunk| chunk.collect::<String>())
-                .all_equal()
+            multiplier * (first + last) * (last - first + 1) / 2
+        }
+    }
+
+    fn list_invalid(&self, rule: &InvalidIdRule) -> Vec<usize> {
+        #[cfg(feature = "parallel")]
+        let ids = (self.start..=self.end).into_par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let ids = self.start..=self.end;
+        ids.filter(|&id| rule.is_invalid(id)).collect()
+    }
+}
+
+enum Pattern {
+    Repeated,
+    Palindrome,
+    PrefixedRepeat(String),
+}
+
+struct InvalidIdRule {
+    base: u32,
+    min_repeats: usize,
+    max_repeats: usize,
+    pattern: Pattern,
+}
+
+impl InvalidIdRule {
+    fn new(args: &[String]) -> Result<InvalidIdRule, String> {
+        let mut rule = InvalidIdRule {
+            base: 10,
+            min_repeats: 2,
+            max_repeats: usize::MAX,
+            pattern: Pattern::Repeated,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} needs a value", arg));
+            let number = |value: &String| {
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid value {:?} for {}", value, arg))
+            };
+            match arg.as_str() {
+                "--base" => rule.base = u32::try_from(number(value()?)?).unwrap_or(0),
+                "--min-repeats" => rule.min_repeats = number(value()?)?,
+                "--max-repeats" => rule.max_repeats = number(value()?)?,
+                "--palindrome" => rule.pattern = Pattern::Palindrome,
+                "--prefix" => rule.pattern = Pattern::PrefixedRepeat(value()?.clone()),
+                _ => return Err(format!("invalid option {}", arg)),
+            }
         }
+        if !(2..=36).contains(&rule.base) {
+            return Err("base must be between 2 and 36".to_string());
+        }
+        Ok(rule)
+    }
+
+    fn to_digits(&self, mut id: usize) -> String {
+        let mut digits = vec![];
+        loop {
+            digits.push(char::from_digit((id % self.base as usize) as u32, self.base).unwrap());
+            id /= self.base as usize;
+            if id == 0 {
+                break;
+            }
+        }
+        digits.iter().rev().collect()
+    }
+
+    fn is_invalid(&self, id: usize) -> bool {
+        let digits = self.to_digits(id);
+        match &self.pattern {
+            Pattern::Repeated => self.is_repeated(&digits),
+            Pattern::Palindrome => digits.chars().eq(digits.chars().rev()),
+            Pattern::PrefixedRepeat(prefix) => digits
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| self.is_repeated(rest)),
+        }
+    }
+
+    fn is_repeated(&self, digits: &str) -> bool {
+        let len = digits.len();
+        (self.min_repeats.max(2)..=self.max_repeats.min(len))
+            .filter(|repeats| len.is_multiple_of(*repeats))
+            .any(|repeats| digits.as_bytes().chunks(len / repeats).all_equal())
     }
 }
 
-fn part1(ranges: &Vec<Range>) -> usize {
-    ranges.iter().map(Range::count_invalid).sum()
+#[cfg(not(feature = "parallel"))]
+fn sum_ranges(ranges: &[Range], count: impl Fn(&Range) -> u128 + Sync + Send) -> u128 {
+    ranges.iter().map(count).sum()
+}
+
+#[cfg(feature = "parallel")]
+fn sum_ranges(ranges: &[Range], count: impl Fn(&Range) -> u128 + Sync + Send) -> u128 {
+    ranges.par_iter().map(count).sum()
+}
+
+fn part1(ranges: &Vec<Range>) -> u128 {
+    sum_ranges(ranges, Range::count_invalid)
 }
 
-fn part2(ranges: &Vec<Range>) -> usize {
-    ranges.iter().map(Range::count_all_invalid).sum()
+fn part2(ranges: &Vec<Range>) -> u128 {
+    sum_ranges(ranges, Range::count_all_invalid)
 }
 pub(crate) fn solve() {
     let contents = fs::read_to_string("2.txt").unwrap();
-    let ranges = contents.split(",").map(|data| Range::new(data)).collect();
+    let ranges = match Token::new(&contents)
+        .split(",")
+        .map(Range::new)
+        .try_collect()
+    {
+        Ok(ranges) => ranges,
+        Err(error) => return eprintln!("{}", error),
+    };
     println!("{}", part1(&ranges));
     println!("{}", part2(&ranges));
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "--list") {
+        if args.len() < 2 {
+            return eprintln!(
+                "usage: --list <start-end> [--base N] [--min-repeats N] [--max-repeats N] [--palindrome] [--prefix P]"
+            );
+        }
+        let rule = match InvalidIdRule::new(&args[2..]) {
+            Ok(rule) => rule,
+            Err(error) => return eprintln!("{}", error),
+        };
+        let range = match Range::new(Token::new(&args[1])) {
+            Ok(range) => range,
+            Err(error) => return eprintln!("{}", error),
+        };
+        for id in range.list_invalid(&rule) {
+            println!("{} {}", id, rule.to_digits(id));
+        }
+    }
 }
@@ -1,67 +1,257 @@
-use std::fs;
+use crate::parse::{ParseError, Token};
+use itertools::Itertools;
+use num_bigint::BigUint;
+use std::io::IsTerminal;
+use std::{env, fs, io};
+
 struct Bank {
-    batteries: Vec<usize>,
+    batteries: Vec<u32>,
+    base: u32,
+}
+
+#[derive(Clone, Copy)]
+enum Objective {
+    Max,
+    Min,
+}
+
+#[derive(Clone, Copy)]
+enum Strategy {
+    // rescan the remaining window for every digit, O(n * k)
+    Window,
+    // monotonic stack, O(n)
+    Stack,
+}
+
+#[derive(Clone, Copy)]
+enum Highlight {
+    Ansi,
+    Brackets,
+}
+
+#[derive(PartialEq, Debug)]
+struct Selection {
+    indices: Vec<usize>,
+    value: BigUint,
 }
 
 impl Bank {
-    fn new(line: &str) -> Self {
-        Self {
-            batteries: line
-                .chars()
-                .map(|x| x.to_string())
-                .map(|x| x.parse::<usize>())
-                .map(Result::unwrap)
-                .collect(),
-        }
+    fn new(line: Token) -> Result<Self, ParseError> {
+        Self::with_base(line, 10)
     }
 
-    fn find_max_two(&self) -> usize {
-        let len = self.batteries.len();
-        let max = self.find_max_index(&self.batteries);
-        if max == len - 1 {
-            // max is the right-most
-            let left = self.batteries[..max].iter().max().unwrap();
-            10 * left + self.batteries[max]
-        } else {
-            let right = self.batteries[max + 1..].iter().max().unwrap();
-            10 * self.batteries[max] + right
-        }
+    fn with_base(line: Token, base: u32) -> Result<Self, ParseError> {
+        let batteries = line
+            .text()
+            .char_indices()
+            .map(|(offset, x)| {
+                x.to_digit(base)
+                    .ok_or_else(|| line.at(offset).error("invalid battery"))
+            })
+            .try_collect()?;
+        Ok(Self { batteries, base })
+    }
+
+    fn select(&self, needed: usize, objective: Objective, strategy: Strategy) -> Selection {
+        let indices = match strategy {
+            Strategy::Window => self.select_window(needed, objective),
+            Strategy::Stack => self.select_stack(needed, objective),
+        };
+        let value = indices.iter().fold(BigUint::ZERO, |acc, &index| {
+            acc * self.base + self.batteries[index]
+        });
+        Selection { indices, value }
     }
 
-    fn find_max_n(&self, needed: usize) -> usize {
+    fn select_window(&self, needed: usize, objective: Objective) -> Vec<usize> {
+        // with a fixed number of digits, the best value is picked greedily from the most significant digit
+        let mut indices = vec![];
         let mut start_position = 0;
-        let mut result = 0;
         for still_needed in (0..needed).rev() {
             let end_position = self.batteries.len() - still_needed;
             let search_space = &self.batteries[start_position..end_position];
-            let max_index = self.find_max_index(search_space);
-            start_position = start_position + max_index + 1;
-            result = result * 10 + search_space[max_index]
+            let index = start_position + self.find_best_index(search_space, objective);
+            indices.push(index);
+            start_position = index + 1;
+        }
+        indices
+    }
+
+    fn select_stack(&self, needed: usize, objective: Objective) -> Vec<usize> {
+        let mut stack: Vec<usize> = Vec::with_capacity(self.batteries.len());
+        let mut can_drop = self.batteries.len() - needed;
+        for (index, battery) in self.batteries.iter().enumerate() {
+            // drop earlier digits as long as the current one is strictly better and enough digits remain
+            while can_drop > 0
+                && stack.last().is_some_and(|&top| match objective {
+                    Objective::Max => self.batteries[top] < *battery,
+                    Objective::Min => self.batteries[top] > *battery,
+                })
+            {
+                stack.pop();
+                can_drop -= 1;
+            }
+            stack.push(index);
         }
-        result
+        stack.truncate(needed);
+        stack
     }
-    fn find_max_index(&self, search_space: &[usize]) -> usize {
-        // find index of first occurrence of max value
-        search_space
+
+    fn explain(&self, selection: &Selection, highlight: Highlight) -> String {
+        self.batteries
             .iter()
             .enumerate()
-            .rev()
-            .max_by_key(|(idx, v)| *v)
-            .map(|(idx, _)| idx)
-            .unwrap()
+            .map(|(index, &battery)| {
+                let digit = char::from_digit(battery, self.base).unwrap();
+                match (selection.indices.contains(&index), highlight) {
+                    (false, _) => digit.to_string(),
+                    (true, Highlight::Ansi) => format!("\x1b[1;32m{}\x1b[0m", digit),
+                    (true, Highlight::Brackets) => format!("[{}]", digit),
+                }
+            })
+            .collect()
+    }
+
+    fn find_best_index(&self, search_space: &[u32], objective: Objective) -> usize {
+        // find index of first occurrence of best value
+        let best = match objective {
+            Objective::Max => search_space.iter().max(),
+            Objective::Min => search_space.iter().min(),
+        };
+        search_space.iter().position(|v| Some(v) == best).unwrap()
     }
 }
 
-fn part1(ranges: &Vec<Bank>) -> usize {
-    ranges.iter().map(Bank::find_max_two).sum()
+fn part1(ranges: &Vec<Bank>) -> BigUint {
+    ranges
+        .iter()
+        .map(|x| x.select(2, Objective::Max, Strategy::Stack).value)
+        .sum()
 }
 
-fn part2(ranges: &Vec<Bank>) -> usize {
-    ranges.iter().map(|x| x.find_max_n(12)).sum()
+fn part2(ranges: &Vec<Bank>) -> BigUint {
+    ranges
+        .iter()
+        .map(|x| x.select(12, Objective::Max, Strategy::Stack).value)
+        .sum()
 }
 pub(crate) fn solve() {
     let contents = fs::read_to_string("3.txt").unwrap();
-    let banks = contents.lines().map(|line| Bank::new(line)).collect();
+    let banks: Vec<Bank> = match Token::new(&contents).lines().map(Bank::new).try_collect() {
+        Ok(banks) => banks,
+        Err(error) => return eprintln!("{}", error),
+    };
     println!("{}", part1(&banks));
     println!("{}", part2(&banks));
+    if env::args().nth(1).as_deref() == Some("--explain") {
+        let highlight = if io::stdout().is_terminal() {
+            Highlight::Ansi
+        } else {
+            Highlight::Brackets
+        };
+        for bank in &banks {
+            for needed in [2, 12] {
+                let selection = bank.select(needed, Objective::Max, Strategy::Stack);
+                let explained = bank.explain(&selection, highlight);
+                println!("{} {}", explained, selection.value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn check_strategies_agree(rounds: usize, seed: u64) {
+        let mut random = Random::new(seed);
+        let mut next = |limit: u64| random.below(limit);
+        for _ in 0..rounds {
+            let base = 2 + next(35) as u32;
+            let length = 1 + next(40) as usize;
+            let line: String = (0..length)
+                .map(|_| char::from_digit(next(base as u64) as u32, base).unwrap())
+                .collect();
+            let bank = Bank::with_base(Token::new(&line), base).unwrap();
+            let needed = next(length as u64 + 1) as usize;
+            for objective in [Objective::Max, Objective::Min] {
+                assert_eq!(
+                    bank.select(needed, objective, Strategy::Window),
+                    bank.select(needed, objective, Strategy::Stack),
+                    "{} in base {}, selecting {}",
+                    line,
+                    base,
+                    needed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn window_and_stack_agree() {
+        check_strategies_agree(100_000, 0x2545F4914F6CDD1D);
+    }
+
+    fn selected(line: &str, needed: usize, objective: Objective) -> (Vec<usize>, BigUint) {
+        let bank = Bank::new(Token::new(line)).unwrap();
+        let selection = bank.select(needed, objective, Strategy::Window);
+        assert_eq!(
+            selection,
+            bank.select(needed, objective, Strategy::Stack),
+            "{} selecting {}",
+            line,
+            needed
+        );
+        (selection.indices, selection.value)
+    }
+
+    #[test]
+    fn selecting_nothing() {
+        for line in ["", "8", "31415"] {
+            for objective in [Objective::Max, Objective::Min] {
+                assert_eq!(selected(line, 0, objective), (vec![], BigUint::ZERO));
+            }
+        }
+    }
+
+    #[test]
+    fn selecting_every_battery() {
+        for objective in [Objective::Max, Objective::Min] {
+            assert_eq!(
+                selected("4719", 4, objective),
+                (vec![0, 1, 2, 3], BigUint::from(4719u32))
+            );
+        }
+    }
+
+    #[test]
+    fn equal_batteries_are_taken_from_the_front() {
+        for objective in [Objective::Max, Objective::Min] {
+            assert_eq!(
+                selected("7777", 2, objective),
+                (vec![0, 1], BigUint::from(77u32))
+            );
+        }
+    }
+
+    #[test]
+    fn monotonic_banks() {
+        assert_eq!(
+            selected("123456", 3, Objective::Max),
+            (vec![3, 4, 5], BigUint::from(456u32))
+        );
+        assert_eq!(
+            selected("123456", 3, Objective::Min),
+            (vec![0, 1, 2], BigUint::from(123u32))
+        );
+        assert_eq!(
+            selected("987654", 3, Objective::Max),
+            (vec![0, 1, 2], BigUint::from(987u32))
+        );
+        assert_eq!(
+            selected("987654", 3, Objective::Min),
+            (vec![3, 4, 5], BigUint::from(654u32))
+        );
+    }
 }
@@ -1,82 +1,375 @@
-use std::collections::{HashMap, HashSet};
-use std::fs;
+use crate::grid::Grid;
+use crate::parse::{ParseError, Token};
+use gif::{Encoder, Frame, Repeat};
+use std::borrow::Cow;
+use std::fs::File;
+use std::str::FromStr;
+use std::time::Duration;
+use std::{env, fs, thread};
 
+#[derive(Clone)]
+enum Neighbourhood {
+    VonNeumann,
+    Moore,
+    // every cell within the given Chebyshev distance
+    Radius(i64),
+    // hexagonal cells in "odd-r" layout, odd rows are shifted half a cell to the right
+    Hexagonal,
+}
+
+impl Neighbourhood {
+    fn new(name: &str) -> Result<Self, String> {
+        match name.split_once(':') {
+            Some(("radius", radius)) => radius
+                .parse()
+                .map(Neighbourhood::Radius)
+                .map_err(|_| format!("invalid radius {:?}", radius)),
+            _ => match name {
+                "von-neumann" => Ok(Neighbourhood::VonNeumann),
+                "moore" => Ok(Neighbourhood::Moore),
+                "hex" => Ok(Neighbourhood::Hexagonal),
+                _ => Err(format!(
+                    "invalid neighbourhood {:?}, expected von-neumann, moore, hex or radius:N",
+                    name
+                )),
+            },
+        }
+    }
+
+    fn deltas(&self, row: i64) -> Vec<(i64, i64)> {
+        match self {
+            Neighbourhood::VonNeumann => vec![(0, -1), (0, 1), (-1, 0), (1, 0)],
+            Neighbourhood::Moore => Neighbourhood::Radius(1).deltas(row),
+            Neighbourhood::Radius(radius) => (-radius..=*radius)
+                .flat_map(|dr| (-radius..=*radius).map(move |dc| (dr, dc)))
+                .filter(|&delta| delta != (0, 0))
+                .collect(),
+            Neighbourhood::Hexagonal => {
+                let shift = row.rem_euclid(2);
+                vec![
+                    (0, -1),
+                    (0, 1),
+                    (-1, shift - 1),
+                    (-1, shift),
+                    (1, shift - 1),
+                    (1, shift),
+                ]
+            }
+        }
+    }
+}
+
+struct Options {
+    neighbourhood: Neighbourhood,
+    threshold: usize,
+    delay: Option<Duration>,
+    gif_path: Option<String>,
+}
+
+impl Options {
+    fn new(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            neighbourhood: Neighbourhood::Moore,
+            threshold: 4,
+            delay: None,
+            gif_path: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = args.next().ok_or(format!("{} needs a value", arg))?;
+            match arg.as_str() {
+                "--neighbourhood" => options.neighbourhood = Neighbourhood::new(value)?,
+                "--threshold" => options.threshold = Self::number(arg, value)?,
+                "--animate" => {
+                    options.delay = Some(Duration::from_millis(Self::number(arg, value)?))
+                }
+                "--gif" => options.gif_path = Some(value.clone()),
+                _ => return Err(format!("invalid option {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+
+    fn number<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+        value
+            .parse()
+            .map_err(|_| format!("invalid value {:?} for {}", value, arg))
+    }
+}
+
+#[derive(Clone)]
 struct Board {
-    neighbours: HashMap<(i64, i64), usize>,
+    width: usize,
+    // one bitset per row, bit `col % 64` of word `col / 64` marks a roll
+    rows: Vec<Vec<u64>>,
+    neighbourhood: Neighbourhood,
+    // a roll can be moved when it has fewer neighbours than this
+    threshold: usize,
 }
 
 impl Board {
-    fn new(data: &str) -> Self {
-        let positions: HashSet<(i64, i64)> = data
-            .lines()
-            .enumerate()
-            .map(|(row, line)| {
-                line.chars()
-                    .enumerate()
-                    .filter(|(_, c)| c.eq(&'@'))
-                    .map(move |(col, _)| (row as i64, col as i64))
+    fn new(data: &str, neighbourhood: Neighbourhood, threshold: usize) -> Result<Self, ParseError> {
+        let grid = Grid::parse(Token::new(data), false, |c| match c {
+            '@' => Some(true),
+            '.' => Some(false),
+            _ => None,
+        })?;
+        let width = grid.width();
+        let rows = grid
+            .rows()
+            .map(|cells| {
+                let mut row = vec![0u64; width.div_ceil(64)];
+                for (col, _) in cells.iter().enumerate().filter(|(_, roll)| **roll) {
+                    row[col / 64] |= 1 << (col % 64);
+                }
+                row
             })
-            .flatten()
             .collect();
-        Self {
-            neighbours: positions
-                .iter()
-                .map(|&pos| (pos, Self::find_neighbours(&positions, &pos).len()))
-                .collect(),
-        }
+        Ok(Self {
+            width,
+            rows,
+            neighbourhood,
+            threshold,
+        })
     }
 
     fn count_can_be_moved(&self) -> usize {
-        self.neighbours.values().filter(|&&v| v < 4).count()
+        self.movable()
+            .iter()
+            .flatten()
+            .map(|word| word.count_ones() as usize)
+            .sum()
     }
 
-    fn can_be_moved(&self, pos: &(i64, i64)) -> bool {
-        self.neighbours.get(pos).unwrap() < &4
+    fn movable(&self) -> Vec<Vec<u64>> {
+        let empty = vec![0u64; self.width.div_ceil(64)];
+        (0..self.rows.len())
+            .map(|row| {
+                let neighbours: Vec<Vec<u64>> = self
+                    .neighbourhood
+                    .deltas(row as i64)
+                    .into_iter()
+                    .map(|(dr, dc)| {
+                        let other = row as i64 + dr;
+                        let bits = if other < 0 {
+                            &empty
+                        } else {
+                            self.rows.get(other as usize).unwrap_or(&empty)
+                        };
+                        Self::shift(bits, dc)
+                    })
+                    .collect();
+                let current = &self.rows[row];
+                (0..current.len())
+                    .map(|word| {
+                        let counts = Self::count_bits(neighbours.iter().map(|n| n[word]));
+                        current[word] & !Self::at_least(&counts, self.threshold)
+                    })
+                    .collect()
+            })
+            .collect()
     }
 
-    fn find_neighbours(
-        positions: &HashSet<(i64, i64)>,
-        (row, col): &(i64, i64),
-    ) -> Vec<(i64, i64)> {
-        Self::neighbour_deltas()
-            .iter()
-            .map(|(dr, dc)| (row + dr, col + dc))
-            .filter(|pos| positions.contains(&(pos)))
+    fn count_bits(neighbours: impl Iterator<Item = u64>) -> Vec<u64> {
+        // bit-sliced counter, every bit position counts its own neighbours in binary across planes
+        let mut planes: Vec<u64> = vec![];
+        for neighbour in neighbours {
+            let mut carry = neighbour;
+            for plane in planes.iter_mut() {
+                let next_carry = *plane & carry;
+                *plane ^= carry;
+                carry = next_carry;
+            }
+            if carry != 0 {
+                planes.push(carry);
+            }
+        }
+        planes
+    }
+
+    fn at_least(planes: &[u64], threshold: usize) -> u64 {
+        // compare every bit-sliced count with the threshold, from the most significant plane down
+        let bits = planes.len().max(usize::BITS as usize);
+        let (mut greater, mut equal) = (0u64, !0u64);
+        for i in (0..bits).rev() {
+            let plane = planes.get(i).copied().unwrap_or(0);
+            let expected = if i < usize::BITS as usize && (threshold >> i) & 1 == 1 {
+                !0u64
+            } else {
+                0
+            };
+            greater |= equal & plane & !expected;
+            equal &= !(plane ^ expected);
+        }
+        greater | equal
+    }
+
+    fn shift(row: &[u64], offset: i64) -> Vec<u64> {
+        // bit `col` becomes the value of `col + offset`
+        let words = (offset.unsigned_abs() / 64) as i64;
+        let bits = offset.unsigned_abs() % 64;
+        let word_at = |index: i64| {
+            if index < 0 {
+                0
+            } else {
+                row.get(index as usize).copied().unwrap_or(0)
+            }
+        };
+        (0..row.len() as i64)
+            .map(|word| {
+                if offset >= 0 {
+                    let source = word + words;
+                    let carry = if bits > 0 {
+                        word_at(source + 1) << (64 - bits)
+                    } else {
+                        0
+                    };
+                    (word_at(source) >> bits) | carry
+                } else {
+                    let source = word - words;
+                    let carry = if bits > 0 {
+                        word_at(source - 1) >> (64 - bits)
+                    } else {
+                        0
+                    };
+                    (word_at(source) << bits) | carry
+                }
+            })
             .collect()
     }
 
-    fn remove_movable(&mut self) {
-        let to_remove: Vec<(i64, i64)> = self
-            .neighbours
-            .keys()
-            .filter(|pos| self.can_be_moved(pos))
-            .map(|pos| *pos)
-            .collect();
-        let positions = self.neighbours.keys().map(|x| *x).collect();
-        let to_decrement: Vec<(i64, i64)> = to_remove
-            .iter()
-            .map(|pos| Self::find_neighbours(&positions, pos))
-            .flatten()
-            .collect();
-        for pos in to_decrement {
-            self.neighbours.insert(pos, self.neighbours[&pos] - 1);
+    fn contains(&self, row: i64, col: i64) -> bool {
+        row >= 0
+            && col >= 0
+            && (col as usize) < self.width
+            && self
+                .rows
+                .get(row as usize)
+                .is_some_and(|bits| bits[col as usize / 64] & (1 << (col % 64)) != 0)
+    }
+
+    fn removal_waves(&self) -> Grid<usize> {
+        // like a k-core decomposition, only neighbours of removed rolls are revisited,
+        // wave 0 marks cells which are never removed
+        let width = self.width;
+        let index = |(row, col): (usize, usize)| row * width + col;
+        let rolls = Grid::from_fn(self.rows.len(), width, |row, col| {
+            self.contains(row as i64, col as i64)
+        });
+        // deltas only depend on the parity of the row
+        let deltas = [self.neighbourhood.deltas(0), self.neighbourhood.deltas(1)];
+        let mut neighbours = vec![0; self.rows.len() * width];
+        let mut waves = vec![0; self.rows.len() * width];
+        let mut to_remove = vec![];
+        for position in rolls.positions().filter(|&position| rolls[position]) {
+            let count = rolls
+                .neighbours(position, &deltas[position.0 % 2])
+                .filter(|(_, roll)| **roll)
+                .count();
+            neighbours[index(position)] = count;
+            if count < self.threshold {
+                waves[index(position)] = 1;
+                to_remove.push(position);
+            }
         }
-        for pos in to_remove {
-            self.neighbours.remove(&pos);
+        let mut wave = 1;
+        while !to_remove.is_empty() {
+            let mut next_wave = vec![];
+            for position in to_remove {
+                for (other, roll) in rolls.neighbours(position, &deltas[position.0 % 2]) {
+                    if !*roll || waves[index(other)] != 0 {
+                        continue;
+                    }
+                    let count = &mut neighbours[index(other)];
+                    *count -= 1;
+                    if *count < self.threshold {
+                        waves[index(other)] = wave + 1;
+                        next_wave.push(other);
+                    }
+                }
+            }
+            to_remove = next_wave;
+            wave += 1;
         }
+        Grid::from_fn(self.rows.len(), width, |row, col| waves[index((row, col))])
     }
 
-    fn neighbour_deltas() -> Vec<(i64, i64)> {
-        vec![
-            (0, -1),
-            (0, 1),
-            (-1, 0),
-            (1, 0),
-            (-1, 1),
-            (-1, -1),
-            (1, 1),
-            (1, -1),
-        ]
+    fn render(&self, waves: &Grid<usize>, wave: usize) -> Grid<char> {
+        // '@' still standing, 'x' removed in this wave, '.' empty or removed earlier
+        Grid::from_fn(self.rows.len(), self.width, |row, col| {
+            match waves[(row, col)] {
+                _ if !self.contains(row as i64, col as i64) => '.',
+                0 => '@',
+                removed if removed < wave => '.',
+                removed if removed == wave => 'x',
+                _ => '@',
+            }
+        })
+    }
+
+    fn frames(&self) -> Vec<Grid<char>> {
+        let waves = self.removal_waves();
+        let last_wave = waves
+            .positions()
+            .map(|position| waves[position])
+            .max()
+            .unwrap_or(0);
+        (0..=last_wave)
+            .map(|wave| self.render(&waves, wave))
+            .collect()
+    }
+
+    fn animate(&self, delay: Duration) {
+        for frame in self.frames() {
+            println!("\x1b[2J\x1b[H{}", frame);
+            thread::sleep(delay);
+        }
+    }
+
+    fn write_gif(&self, path: &str) -> Result<(), String> {
+        let scale = 4;
+        // gif frames are at most 65535 pixels in either direction
+        let size = |cells: usize| {
+            u16::try_from(cells * scale).map_err(|_| "board too large for a gif".to_string())
+        };
+        let (width, height) = (size(self.width)?, size(self.rows.len())?);
+        let palette = [0xff, 0xff, 0xff, 0x80, 0x60, 0x40, 0xe0, 0x20, 0x20];
+        let file = File::create(path).map_err(|error| format!("{}: {}", path, error))?;
+        let mut encoder =
+            Encoder::new(file, width, height, &palette).map_err(|error| error.to_string())?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|error| error.to_string())?;
+        for frame in self.frames() {
+            let pixels: Vec<u8> = frame
+                .rows()
+                .flat_map(|row| {
+                    let scaled: Vec<u8> = row
+                        .iter()
+                        .flat_map(|cell| {
+                            let colour = match cell {
+                                '@' => 1,
+                                'x' => 2,
+                                _ => 0,
+                            };
+                            vec![colour; scale]
+                        })
+                        .collect();
+                    scaled.repeat(scale)
+                })
+                .collect();
+            let frame = Frame {
+                width,
+                height,
+                delay: 20,
+                buffer: Cow::Owned(pixels),
+                ..Frame::default()
+            };
+            encoder
+                .write_frame(&frame)
+                .map_err(|error| error.to_string())?;
+        }
+        Ok(())
     }
 }
 fn part1(board: &Board) -> usize {
@@ -84,17 +377,36 @@ fn part1(board: &Board) -> usize {
 }
 
 fn part2(board: &Board) -> usize {
-    let mut work_board = Board {
-        neighbours: board.neighbours.clone(),
-    };
-    while work_board.count_can_be_moved() > 0 {
-        work_board.remove_movable()
-    }
-    board.neighbours.len() - work_board.neighbours.len()
+    let waves = board.removal_waves();
+    waves
+        .positions()
+        .filter(|&position| waves[position] > 0)
+        .count()
 }
 pub(crate) fn solve() {
     let contents = fs::read_to_string("4.txt").unwrap();
-    let board = Board::new(&contents);
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match Options::new(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            return eprintln!(
+                "usage: [--neighbourhood von-neumann|moore|hex|radius:N] [--threshold N] [--animate MILLIS] [--gif PATH]"
+            );
+        }
+    };
+    let board = match Board::new(&contents, options.neighbourhood, options.threshold) {
+        Ok(board) => board,
+        Err(error) => return eprintln!("{}", error),
+    };
+    if let Some(delay) = options.delay {
+        board.animate(delay);
+    }
+    if let Some(path) = &options.gif_path
+        && let Err(error) = board.write_gif(path)
+    {
+        return eprintln!("{}", error);
+    }
     println!("{}", part1(&board));
     println!("{}", part2(&board));
 }
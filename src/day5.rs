@@ -1,144 +1,390 @@
+use crate::parse::{ParseError, Token};
 use itertools::Itertools;
 use std::cmp::{max, min};
-use std::fs;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, Write};
 use std::str::FromStr;
+use std::{env, fs, io};
 
-#[derive(PartialEq)]
-struct InclusiveRange {
-    start: usize,
-    stop: usize,
+trait Bound: Copy + Ord + FromStr + Display {
+    fn successor(self) -> Option<Self>;
+    fn predecessor(self) -> Option<Self>;
+    fn distance(self, other: Self) -> u128;
 }
 
-impl InclusiveRange {
-    fn new(line: &str) -> Self {
-        let (start, stop) = line
-            .split("-")
-            .map(usize::from_str)
-            .map(Result::unwrap)
-            .collect_tuple()
-            .unwrap();
-        Self { start, stop }
+macro_rules! impl_bound {
+    ($($t:ty),*) => {
+        $(impl Bound for $t {
+            fn successor(self) -> Option<Self> {
+                self.checked_add(1)
+            }
+
+            fn predecessor(self) -> Option<Self> {
+                self.checked_sub(1)
+            }
+
+            fn distance(self, other: Self) -> u128 {
+                self.abs_diff(other) as u128
+            }
+        })*
+    };
+}
+
+impl_bound!(u64, u128, i64, usize);
+
+#[derive(Debug)]
+enum DatabaseError {
+    Malformed(ParseError),
+    Reversed(ParseError),
+    CountOverflow,
+}
+
+impl From<ParseError> for DatabaseError {
+    fn from(error: ParseError) -> Self {
+        DatabaseError::Malformed(error)
     }
+}
 
-    fn contains(&self, item: &usize) -> bool {
-        item >= &self.start && item <= &self.stop
+impl Display for DatabaseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseError::Malformed(error) => write!(f, "{}", error),
+            DatabaseError::Reversed(error) => write!(f, "{}", error),
+            DatabaseError::CountOverflow => write!(f, "fresh ingredient count exceeds u128"),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone)]
+struct InclusiveRange<T: Bound> {
+    start: T,
+    stop: T,
+}
+
+impl<T: Bound> Display for InclusiveRange<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.stop)
+    }
+}
+
+impl<T: Bound> InclusiveRange<T> {
+    fn new(line: Token) -> Result<Self, DatabaseError> {
+        // skip the first character so that a leading minus sign is not taken as the separator
+        let separator = line
+            .text()
+            .get(1..)
+            .and_then(|rest| rest.find('-'))
+            .ok_or_else(|| line.error("expected \"-\""))?
+            + 1;
+        let (start, stop) = line.split_at(separator).unwrap();
+        let (start, stop): (T, T) = (start.parse()?, stop.at(1).parse()?);
+        if start > stop {
+            return Err(DatabaseError::Reversed(
+                line.error("range ends before it starts"),
+            ));
+        }
+        Ok(Self { start, stop })
     }
 
-    fn overlaps(&self, other: &InclusiveRange) -> bool {
-        self.contains(&other.start) || self.contains(&other.stop)
+    fn contains(&self, item: &T) -> bool {
+        item >= &self.start && item <= &self.stop
     }
 
-    fn can_be_merged_with(&self, other: &InclusiveRange) -> bool {
-        self.overlaps(other) || other.overlaps(self)
+    fn can_be_merged_with(&self, other: &InclusiveRange<T>) -> bool {
+        // touching ranges like 3-5 and 6-8 are merged as well
+        other.stop.successor().is_none_or(|next| self.start <= next)
+            && self.stop.successor().is_none_or(|next| other.start <= next)
     }
 
-    fn combine(&self, other: &InclusiveRange) -> InclusiveRange {
+    fn combine(&self, other: &InclusiveRange<T>) -> InclusiveRange<T> {
         let start = min(other.start, self.start);
         let stop = max(other.stop, self.stop);
         Self { start, stop }
     }
 
-    fn element_count(&self) -> usize {
-        self.stop - self.start + 1
+    fn element_count(&self) -> Option<u128> {
+        self.stop.distance(self.start).checked_add(1)
     }
 }
 
-struct FreshIngredients {
-    fresh: Vec<InclusiveRange>,
+struct IntervalSet<T: Bound> {
+    // sorted, disjoint and non-adjacent
+    ranges: Vec<InclusiveRange<T>>,
 }
 
-impl FreshIngredients {
-    fn new(ranges: &str) -> Self {
-        let fresh = ranges.lines().map(InclusiveRange::new).collect();
-        let compacted = FreshIngredients::compact(fresh);
-        Self { fresh: compacted }
+impl<T: Bound> IntervalSet<T> {
+    fn new(mut ranges: Vec<InclusiveRange<T>>) -> Self {
+        ranges.sort_by_key(|r| (r.start, r.stop));
+        let mut merged: Vec<InclusiveRange<T>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if last.can_be_merged_with(&range) => *last = last.combine(&range),
+                _ => merged.push(range),
+            }
+        }
+        Self { ranges: merged }
     }
 
-    fn contains(&self, item: &usize) -> bool {
-        self.fresh.iter().any(|r| r.contains(item))
+    fn contains(&self, item: &T) -> bool {
+        let index = self.ranges.partition_point(|r| r.stop < *item);
+        self.ranges
+            .get(index)
+            .is_some_and(|range| range.contains(item))
     }
 
-    fn compact(initial: Vec<InclusiveRange>) -> Vec<InclusiveRange> {
-        let (mut compacted_ranges, mut compacted) = FreshIngredients::compact_once(&initial);
-        while compacted {
-            (compacted_ranges, compacted) = FreshIngredients::compact_once(&compacted_ranges);
-        }
-        compacted_ranges
+    fn count_all(&self) -> Option<u128> {
+        self.ranges.iter().try_fold(0u128, |total, range| {
+            total.checked_add(range.element_count()?)
+        })
     }
 
-    fn compact_once(ranges: &Vec<InclusiveRange>) -> (Vec<InclusiveRange>, bool) {
-        let (compacted, did_expand): (Vec<InclusiveRange>, Vec<bool>) = ranges
+    fn insert(&mut self, range: InclusiveRange<T>) {
+        let first = self
+            .ranges
+            .partition_point(|r| !r.can_be_merged_with(&range) && r.stop < range.start);
+        let last = self
+            .ranges
+            .partition_point(|r| r.can_be_merged_with(&range) || r.stop < range.start);
+        let merged = self.ranges[first..last]
             .iter()
-            .map(|item| FreshIngredients::expand_range(item, ranges))
-            .unique_by(|(r, _)| (r.start, r.stop))
-            .unzip();
-        let something_compacted = did_expand.iter().any(|x| *x);
-        (compacted, something_compacted)
-    }
-
-    fn expand_range(
-        current: &InclusiveRange,
-        others: &Vec<InclusiveRange>,
-    ) -> (InclusiveRange, bool) {
-        let mut acc = InclusiveRange {
-            start: current.start,
-            stop: current.stop,
-        };
-        let mut expanded = false;
-        for other in others {
-            if current != other && (acc.can_be_merged_with(other)) {
-                acc = acc.combine(other);
-                expanded = true;
+            .fold(range, |acc, r| acc.combine(r));
+        self.ranges.splice(first..last, [merged]);
+    }
+
+    fn remove(&mut self, range: &InclusiveRange<T>) {
+        let first = self.ranges.partition_point(|r| r.stop < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.stop);
+        if first >= last {
+            return;
+        }
+        let mut remaining = vec![];
+        if self.ranges[first].start < range.start {
+            remaining.push(InclusiveRange {
+                start: self.ranges[first].start,
+                stop: range.start.predecessor().unwrap(),
+            });
+        }
+        if self.ranges[last - 1].stop > range.stop {
+            remaining.push(InclusiveRange {
+                start: range.stop.successor().unwrap(),
+                stop: self.ranges[last - 1].stop,
+            });
+        }
+        self.ranges.splice(first..last, remaining);
+    }
+
+    fn bounds(&self) -> Option<InclusiveRange<T>> {
+        let start = self.ranges.first()?.start;
+        let stop = self.ranges.last()?.stop;
+        Some(InclusiveRange { start, stop })
+    }
+
+    fn union(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        IntervalSet::new([&self.ranges[..], &other.ranges[..]].concat())
+    }
+
+    fn intersection(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut ranges = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let start = max(a.start, b.start);
+            let stop = min(a.stop, b.stop);
+            if start <= stop {
+                ranges.push(InclusiveRange { start, stop });
+            }
+            if a.stop < b.stop {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { ranges }
+    }
+
+    fn difference(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        match self.bounds() {
+            Some(bounds) => self.intersection(&other.complement(&bounds)),
+            None => IntervalSet { ranges: vec![] },
+        }
+    }
+
+    fn complement(&self, bound: &InclusiveRange<T>) -> IntervalSet<T> {
+        let mut ranges = vec![];
+        // None once a range reaches the maximum value, nothing can be left after it
+        let mut next = Some(bound.start);
+        for range in &self.ranges {
+            let Some(start) = next else { break };
+            if range.start > bound.stop {
+                break;
+            }
+            if range.start > start {
+                ranges.push(InclusiveRange {
+                    start,
+                    stop: range.start.predecessor().unwrap(),
+                });
             }
+            next = range.stop.successor().map(|after| max(start, after));
+        }
+        if let Some(start) = next.filter(|start| start <= &bound.stop) {
+            ranges.push(InclusiveRange {
+                start,
+                stop: bound.stop,
+            });
+        }
+        IntervalSet { ranges }
+    }
+
+    fn gaps(&self) -> Vec<InclusiveRange<T>> {
+        match self.bounds() {
+            Some(bounds) => self.complement(&bounds).ranges,
+            None => vec![],
         }
-        (acc, expanded)
+    }
+}
+
+struct FreshIngredients<T: Bound> {
+    fresh: IntervalSet<T>,
+}
+
+impl<T: Bound> FreshIngredients<T> {
+    fn new(ranges: Token) -> Result<Self, DatabaseError> {
+        let fresh = ranges.lines().map(InclusiveRange::new).try_collect()?;
+        Ok(Self {
+            fresh: IntervalSet::new(fresh),
+        })
+    }
+
+    fn contains(&self, item: &T) -> bool {
+        self.fresh.contains(item)
+    }
+
+    fn count_all(&self) -> Result<u128, DatabaseError> {
+        self.fresh.count_all().ok_or(DatabaseError::CountOverflow)
     }
 
-    fn count_all(&self) -> usize {
-        self.fresh.iter().map(InclusiveRange::element_count).sum()
+    fn no_longer_fresh(&self, newer: &FreshIngredients<T>) -> IntervalSet<T> {
+        self.fresh.difference(&newer.fresh)
+    }
+
+    fn insert(&mut self, range: InclusiveRange<T>) {
+        self.fresh.insert(range)
+    }
+
+    fn remove(&mut self, range: &InclusiveRange<T>) {
+        self.fresh.remove(range)
     }
 }
 
-struct IngredientDB {
-    fresh: FreshIngredients,
-    ingredients: Vec<usize>,
+struct IngredientDB<T: Bound> {
+    fresh: FreshIngredients<T>,
+    ingredients: Vec<T>,
 }
 
-impl IngredientDB {
-    fn new(data: &str) -> Self {
-        let (ranges, items) = data.split_once("\n\n").unwrap();
-        let fresh = FreshIngredients::new(ranges);
-        let ingredients = items
-            .lines()
-            .map(usize::from_str)
-            .map(Result::unwrap)
-            .collect();
-        Self { fresh, ingredients }
+impl<T: Bound> IngredientDB<T> {
+    fn new(data: &str) -> Result<Self, DatabaseError> {
+        let (ranges, items) = Token::new(data).split_once("\n\n")?;
+        let fresh = FreshIngredients::new(ranges)?;
+        let ingredients = items.lines().map(|line| line.parse()).try_collect()?;
+        Ok(Self { fresh, ingredients })
     }
 
-    fn fresh_ingredients(&self) -> Vec<usize> {
+    fn fresh_ingredients(&self) -> Vec<T> {
         self.ingredients
             .iter()
             .filter(|item| self.fresh.contains(item))
-            .map(usize::clone)
+            .copied()
             .collect()
     }
 
-    fn count_all_fresh(&self) -> usize {
+    fn count_all_fresh(&self) -> Result<u128, DatabaseError> {
         self.fresh.count_all()
     }
+
+    fn stream(&mut self, input: impl BufRead, mut output: impl Write) {
+        // "+a-b" and "-a-b" update the fresh ranges, any other line is an ingredient id,
+        // a minus without a range after it is the sign of a negative id
+        for (number, line) in input.lines().map(Result::unwrap).enumerate() {
+            let line = Token::new(&line).on_line(number).trim();
+            let removal = line
+                .strip_prefix("-")
+                .ok()
+                .filter(|range| range.text().get(1..).is_some_and(|rest| rest.contains('-')));
+            let result = if let Ok(range) = line.strip_prefix("+") {
+                InclusiveRange::new(range).map(|range| self.fresh.insert(range))
+            } else if let Some(range) = removal {
+                InclusiveRange::new(range).map(|range| self.fresh.remove(&range))
+            } else if let Ok(item) = line.parse::<T>() {
+                let state = if self.fresh.contains(&item) {
+                    "fresh"
+                } else {
+                    "spoiled"
+                };
+                writeln!(output, "{} {}", item, state).unwrap();
+                Ok(())
+            } else {
+                Err(DatabaseError::Malformed(
+                    line.error("expected an id or a range update"),
+                ))
+            };
+            if let Err(error) = result {
+                writeln!(output, "{}", error).unwrap();
+            }
+        }
+    }
+
+    fn became_stale(&self, newer: &IngredientDB<T>) -> Vec<T> {
+        let stale = self.fresh.no_longer_fresh(&newer.fresh);
+        self.ingredients
+            .iter()
+            .filter(|item| stale.contains(item))
+            .copied()
+            .collect()
+    }
 }
 
-fn part1(db: &IngredientDB) -> usize {
+fn part1<T: Bound>(db: &IngredientDB<T>) -> usize {
     db.fresh_ingredients().len()
 }
 
-fn part2(db: &IngredientDB) -> usize {
+fn part2<T: Bound>(db: &IngredientDB<T>) -> Result<u128, DatabaseError> {
     db.count_all_fresh()
 }
 pub(crate) fn solve() {
     let contents = fs::read_to_string("5.txt").unwrap();
-    let db = IngredientDB::new(&contents);
+    let mut db = match IngredientDB::<u64>::new(&contents) {
+        Ok(db) => db,
+        Err(error) => return eprintln!("{}", error),
+    };
     println!("{}", part1(&db));
-    println!("{}", part2(&db));
+    match part2(&db) {
+        Ok(count) => println!("{}", count),
+        Err(error) => eprintln!("{}", error),
+    }
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect_vec()[..] {
+        ["--stream"] => db.stream(io::stdin().lock(), io::stdout().lock()),
+        ["--gaps"] => {
+            for gap in db.fresh.fresh.gaps() {
+                println!("{}", gap);
+            }
+        }
+        ["--compare", path] => {
+            let contents = match fs::read_to_string(path) {
+                Ok(contents) => contents,
+                Err(error) => return eprintln!("{}: {}", path, error),
+            };
+            let newer = match IngredientDB::new(&contents) {
+                Ok(newer) => newer,
+                Err(error) => return eprintln!("{}", error),
+            };
+            for range in db.fresh.no_longer_fresh(&newer.fresh).ranges {
+                println!("{}", range);
+            }
+            for id in db.became_stale(&newer) {
+                println!("{}", id);
+            }
+        }
+        _ => {}
+    }
 }
@@ -1,10 +1,22 @@
-use std::fs;
+use crate::parse::ParseError;
+use itertools::Itertools;
+use num_bigint::BigUint;
+use num_traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub};
+use std::cmp::{max, min};
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+use std::{env, fs};
 
 #[derive(Clone)]
 enum Operation {
     ADD,
     MUL,
+    SUB,
+    DIV,
+    MIN,
+    MAX,
+    CONCAT,
+    POW,
 }
 
 impl FromStr for Operation {
@@ -14,135 +26,562 @@ impl FromStr for Operation {
         match (s) {
             "+" => Ok(Operation::ADD),
             "*" => Ok(Operation::MUL),
+            "-" => Ok(Operation::SUB),
+            "/" => Ok(Operation::DIV),
+            "<" => Ok(Operation::MIN),
+            ">" => Ok(Operation::MAX),
+            "|" => Ok(Operation::CONCAT),
+            "^" => Ok(Operation::POW),
             _ => Err(()),
         }
     }
 }
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Operation::ADD => "+",
+            Operation::MUL => "*",
+            Operation::SUB => "-",
+            Operation::DIV => "/",
+            Operation::MIN => "<",
+            Operation::MAX => ">",
+            Operation::CONCAT => "|",
+            Operation::POW => "^",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Operation {
+    fn precedence(&self) -> usize {
+        match self {
+            Operation::MIN | Operation::MAX => 0,
+            Operation::CONCAT => 1,
+            Operation::ADD | Operation::SUB => 2,
+            Operation::MUL | Operation::DIV => 3,
+            Operation::POW => 4,
+        }
+    }
+
+    fn right_associative(&self) -> bool {
+        // 2 ^ 3 ^ 2 is 2 ^ 9
+        matches!(self, Operation::POW)
+    }
+
+    fn apply<T: Number>(&self, left: T, right: T) -> Result<T, CalculationError> {
+        let result = match self {
+            Operation::ADD => left.checked_add(&right),
+            Operation::MUL => left.checked_mul(&right),
+            Operation::SUB => left.checked_sub(&right),
+            Operation::DIV if right == T::from_usize(0) => {
+                return Err(CalculationError::DivisionByZero);
+            }
+            Operation::DIV => left.checked_div(&right),
+            Operation::MIN => Some(min(left, right)),
+            Operation::MAX => Some(max(left, right)),
+            Operation::CONCAT => T::from_str(&format!("{}{}", left, right)).ok(),
+            Operation::POW => left.checked_power(&right),
+        };
+        result.ok_or(CalculationError::Overflow)
+    }
+}
+
+trait Number:
+    Clone + Ord + Display + FromStr + CheckedAdd + CheckedSub + CheckedMul + CheckedDiv
+{
+    fn from_usize(value: usize) -> Self;
+    fn from_big(value: &BigUint) -> Option<Self>;
+    fn checked_power(&self, exponent: &Self) -> Option<Self>;
+}
+
+impl Number for usize {
+    fn from_usize(value: usize) -> Self {
+        value
+    }
+
+    fn from_big(value: &BigUint) -> Option<Self> {
+        usize::try_from(value).ok()
+    }
+
+    fn checked_power(&self, exponent: &Self) -> Option<Self> {
+        self.checked_pow(u32::try_from(*exponent).ok()?)
+    }
+}
+
+impl Number for u128 {
+    fn from_usize(value: usize) -> Self {
+        value as u128
+    }
+
+    fn from_big(value: &BigUint) -> Option<Self> {
+        u128::try_from(value).ok()
+    }
+
+    fn checked_power(&self, exponent: &Self) -> Option<Self> {
+        self.checked_pow(u32::try_from(*exponent).ok()?)
+    }
+}
+
+impl Number for BigUint {
+    fn from_usize(value: usize) -> Self {
+        BigUint::from(value)
+    }
+
+    fn from_big(value: &BigUint) -> Option<Self> {
+        Some(value.clone())
+    }
+
+    fn checked_power(&self, exponent: &Self) -> Option<Self> {
+        Some(self.pow(u32::try_from(exponent).ok()?))
+    }
+}
+
+#[derive(Debug)]
+enum CalculationError {
+    Overflow,
+    DivisionByZero,
+}
+
+impl Display for CalculationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CalculationError::Overflow => write!(f, "result does not fit the number type"),
+            CalculationError::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+enum Expression {
+    // operands are parsed at full size, they only have to fit the number type once evaluated
+    Number(BigUint),
+    Apply(Operation, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    fn new(numbers: &[BigUint], operations: &[Operation]) -> Self {
+        // shunting-yard, there is one operation between every two numbers
+        let mut operands = vec![Expression::Number(numbers[0].clone())];
+        let mut pending: Vec<&Operation> = vec![];
+        for (operation, number) in operations.iter().zip(&numbers[1..]) {
+            while pending.last().is_some_and(|top| {
+                top.precedence() > operation.precedence()
+                    || (top.precedence() == operation.precedence()
+                        && !operation.right_associative())
+            }) {
+                Self::reduce(&mut operands, pending.pop().unwrap());
+            }
+            pending.push(operation);
+            operands.push(Expression::Number(number.clone()));
+        }
+        while let Some(operation) = pending.pop() {
+            Self::reduce(&mut operands, operation);
+        }
+        operands.pop().unwrap()
+    }
+
+    fn reduce(operands: &mut Vec<Expression>, operation: &Operation) {
+        let right = operands.pop().unwrap();
+        let left = operands.pop().unwrap();
+        operands.push(Expression::Apply(
+            operation.clone(),
+            Box::new(left),
+            Box::new(right),
+        ));
+    }
+
+    fn evaluate<T: Number>(&self) -> Result<T, CalculationError> {
+        match self {
+            Expression::Number(number) => T::from_big(number).ok_or(CalculationError::Overflow),
+            Expression::Apply(operation, left, right) => {
+                operation.apply(left.evaluate()?, right.evaluate()?)
+            }
+        }
+    }
+
+    fn needs_parentheses(&self, parent: &Operation, is_right: bool) -> bool {
+        match self {
+            Expression::Number(_) => false,
+            Expression::Apply(operation, _, _) => {
+                operation.precedence() < parent.precedence()
+                    || (operation.precedence() == parent.precedence()
+                        && is_right != parent.right_associative())
+            }
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Number(number) => write!(f, "{}", number),
+            Expression::Apply(operation, left, right) => {
+                if left.needs_parentheses(operation, false) {
+                    write!(f, "({})", left)?;
+                } else {
+                    write!(f, "{}", left)?;
+                }
+                write!(f, " {} ", operation)?;
+                if right.needs_parentheses(operation, true) {
+                    write!(f, "({})", right)
+                } else {
+                    write!(f, "{}", right)
+                }
+            }
+        }
+    }
+}
+
 struct Equation {
-    numbers: Vec<usize>,
-    operation: Operation,
+    expression: Expression,
+    // columns of the problem in the worksheet, end exclusive
+    span: (usize, usize),
 }
 
 impl Equation {
-    fn calculate(&self) -> usize {
-        match (self.operation) {
-            Operation::ADD => self.numbers.iter().sum(),
-            Operation::MUL => self.numbers.iter().product(),
+    fn calculate<T: Number>(&self) -> Result<T, CalculationError> {
+        self.expression.evaluate()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Reading {
+    // every line of a block holds one number
+    Rows,
+    // every column of a block holds one number
+    Columns,
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+#[derive(Clone, Copy)]
+enum DigitOrder {
+    TopToBottom,
+    BottomToTop,
+}
+
+#[derive(Clone, Copy)]
+enum OperatorRow {
+    Top,
+    Bottom,
+}
+
+#[derive(Clone, Copy)]
+struct Layout {
+    reading: Reading,
+    // order of the characters in a row, for columns the order of the numbers
+    direction: Direction,
+    // order of the characters in a column, for rows the order of the numbers
+    digits: DigitOrder,
+    operator_row: OperatorRow,
+}
+
+impl Layout {
+    const ROWS: Layout = Layout {
+        reading: Reading::Rows,
+        direction: Direction::LeftToRight,
+        digits: DigitOrder::TopToBottom,
+        operator_row: OperatorRow::Bottom,
+    };
+
+    const COLUMNS: Layout = Layout {
+        reading: Reading::Columns,
+        ..Layout::ROWS
+    };
+
+    fn new(descriptor: &str) -> Result<Layout, String> {
+        // comma separated, e.g. "columns,right-to-left,operators-top", unspecified parts are taken from rows
+        let mut layout = Layout::ROWS;
+        for part in descriptor.split(',') {
+            match part {
+                "rows" => layout.reading = Reading::Rows,
+                "columns" => layout.reading = Reading::Columns,
+                "left-to-right" => layout.direction = Direction::LeftToRight,
+                "right-to-left" => layout.direction = Direction::RightToLeft,
+                "top-to-bottom" => layout.digits = DigitOrder::TopToBottom,
+                "bottom-to-top" => layout.digits = DigitOrder::BottomToTop,
+                "operators-top" => layout.operator_row = OperatorRow::Top,
+                "operators-bottom" => layout.operator_row = OperatorRow::Bottom,
+                _ => return Err(format!("invalid layout part {:?}", part)),
+            }
+        }
+        Ok(layout)
+    }
+
+    fn number_lines(&self, height: usize) -> Vec<usize> {
+        let lines = match self.operator_row {
+            OperatorRow::Top => 1..height,
+            OperatorRow::Bottom => 0..height - 1,
+        };
+        match self.digits {
+            DigitOrder::TopToBottom => lines.collect(),
+            DigitOrder::BottomToTop => lines.rev().collect(),
+        }
+    }
+
+    fn operator_line(&self, height: usize) -> usize {
+        match self.operator_row {
+            OperatorRow::Top => 0,
+            OperatorRow::Bottom => height - 1,
+        }
+    }
+
+    fn columns(&self, start: usize, end: usize) -> Vec<usize> {
+        match self.direction {
+            Direction::LeftToRight => (start..end).collect(),
+            Direction::RightToLeft => (start..end).rev().collect(),
         }
     }
 }
+
 struct Equations {
     equations: Vec<Equation>,
 }
 
 impl Equations {
-    fn new(operations: &Vec<Operation>, numbers: &Vec<Vec<usize>>) -> Self {
-        let equations = operations
+    fn parse(data: &str) -> Result<Equations, ParseError> {
+        Self::parse_with(data, &Layout::ROWS)
+    }
+
+    fn parse_columns(data: &str) -> Result<Equations, ParseError> {
+        Self::parse_with(data, &Layout::COLUMNS)
+    }
+
+    fn parse_with(data: &str, layout: &Layout) -> Result<Equations, ParseError> {
+        let grid = Self::grid(data);
+        if grid.is_empty() {
+            return Err(ParseError::new(0, 0, "empty worksheet"));
+        }
+        let operator_line = layout.operator_line(grid.len());
+        let lines = layout.number_lines(grid.len());
+        let equations = Self::blocks(&grid)
+            .into_iter()
+            .map(|(start, end)| {
+                let columns = layout.columns(start, end);
+                let numbers = match layout.reading {
+                    Reading::Rows => Self::parse_row_numbers(&grid, &lines, &columns)?,
+                    Reading::Columns => Self::parse_column_numbers(&grid, &lines, &columns)?,
+                };
+                if numbers.is_empty() {
+                    return Err(ParseError::new(
+                        operator_line,
+                        start,
+                        "problem without numbers",
+                    ));
+                }
+                let operations = Self::parse_operations(
+                    &grid[operator_line],
+                    operator_line,
+                    start,
+                    end,
+                    numbers.len(),
+                )?;
+                Ok(Equation {
+                    expression: Expression::new(&numbers, &operations),
+                    span: (start, end),
+                })
+            })
+            .try_collect()?;
+        Ok(Equations { equations })
+    }
+
+    fn grid(data: &str) -> Vec<Vec<char>> {
+        // lines may differ in trailing whitespace, pad them all to the same width
+        let lines: Vec<&str> = data.trim_end().lines().map(str::trim_end).collect();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        lines
             .iter()
-            .zip(numbers)
-            .map(|(operation, numbers)| Equation {
-                operation: operation.clone(),
-                numbers: numbers.clone(),
+            .map(|line| line.chars().pad_using(width, |_| ' ').collect())
+            .collect()
+    }
+
+    fn blocks(grid: &[Vec<char>]) -> Vec<(usize, usize)> {
+        // problems are separated by columns which are blank in every line
+        let width = grid.first().map_or(0, Vec::len);
+        (0..width)
+            .chunk_by(|&column| grid.iter().all(|line| line[column] == ' '))
+            .into_iter()
+            .filter(|(blank, _)| !blank)
+            .map(|(_, mut columns)| {
+                let start = columns.next().unwrap();
+                (start, columns.last().unwrap_or(start) + 1)
             })
-            .collect();
-        Equations { equations }
+            .collect()
     }
-}
 
-impl Equations {
-    fn parse(data: &str) -> Equations {
-        let operations = Self::parse_operations(data);
-        let numbers = Self::parse_row_numbers(data);
-        Equations::new(&operations, &numbers)
-    }
-
-    fn parse_columns(data: &str) -> Equations {
-        let operations = Self::parse_operations(data);
-        let numbers = Self::parse_column_numbers(data);
-        Equations::new(&operations, &numbers)
-    }
-
-    fn parse_row_numbers(data: &str) -> Vec<Vec<usize>> {
-        let row_numbers: Vec<Vec<usize>> = data
-            .lines()
-            .rev()
-            .skip(1)
-            .map(|line| {
-                line.split_ascii_whitespace()
-                    .map(|n| n.parse::<usize>().unwrap())
-                    .collect()
+    fn parse_row_numbers(
+        grid: &[Vec<char>],
+        lines: &[usize],
+        columns: &[usize],
+    ) -> Result<Vec<BigUint>, ParseError> {
+        lines
+            .iter()
+            .map(|&line| (line, columns.iter().map(move |&column| grid[line][column])))
+            .filter(|(_, cells)| cells.clone().any(|c| c != ' '))
+            .map(|(line, cells)| {
+                let text: String = cells.clone().collect();
+                text.trim().parse().map_err(|_| {
+                    let offset = Self::first_invalid(cells);
+                    ParseError::new(line, columns[offset], "invalid number")
+                })
             })
-            .collect();
-        Self::transpose(row_numbers)
+            .collect()
     }
 
-    fn transpose(row_numbers: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
-        let num_cols = row_numbers.first().unwrap().len();
-        let mut row_iters: Vec<_> = row_numbers.into_iter().map(Vec::into_iter).collect();
-        (0..num_cols)
-            .map(|_| row_iters.iter_mut().map(|it| it.next().unwrap()).collect())
+    fn parse_column_numbers(
+        grid: &[Vec<char>],
+        lines: &[usize],
+        columns: &[usize],
+    ) -> Result<Vec<BigUint>, ParseError> {
+        columns
+            .iter()
+            .map(|&column| (column, lines.iter().map(move |&line| grid[line][column])))
+            .filter(|(_, cells)| cells.clone().any(|c| c != ' '))
+            .map(|(column, cells)| {
+                let text: String = cells.clone().collect();
+                text.trim().parse().map_err(|_| {
+                    let offset = Self::first_invalid(cells);
+                    ParseError::new(lines[offset], column, "invalid number")
+                })
+            })
             .collect()
     }
 
-    fn parse_column_numbers(data: &str) -> Vec<Vec<usize>> {
-        let digits: Vec<Vec<char>> = data
-            .lines()
-            .rev()
-            .skip(1)
-            .map(|line| line.chars().collect())
-            .collect();
-        let mut number_sets = vec![];
-        let mut current_set = vec![];
-        for column_index in 0..digits[0].len() {
-            let parsed = Self::parse_one_column_number(column_index, &digits);
-            if parsed.is_some() {
-                current_set.push(parsed.unwrap());
-            } else {
-                number_sets.push(current_set);
-                current_set = vec![];
-            }
-        }
-        number_sets.push(current_set);
-        number_sets
+    fn first_invalid(cells: impl Iterator<Item = char> + Clone) -> usize {
+        // point at the first character which is not a digit, or at the number itself
+        cells
+            .clone()
+            .position(|c| c != ' ' && !c.is_ascii_digit())
+            .or_else(|| cells.clone().position(|c| c != ' '))
+            .unwrap_or(0)
     }
 
-    fn parse_one_column_number(column: usize, digits: &Vec<Vec<char>>) -> Option<usize> {
-        let reassembled = digits
-            .iter()
-            .map(|line| line.get(column).unwrap_or(&' '))
-            .rev()
-            .collect::<String>();
-        if reassembled.trim().is_empty() {
-            None
-        } else {
-            Some(reassembled.trim().parse().unwrap())
+    fn parse_operations(
+        operations: &[char],
+        line: usize,
+        start: usize,
+        end: usize,
+        count: usize,
+    ) -> Result<Vec<Operation>, ParseError> {
+        // either one operator for the whole problem, or one between every two numbers
+        let text: String = operations[start..end].iter().collect();
+        let parsed: Vec<Operation> = text
+            .split_whitespace()
+            .map(Operation::from_str)
+            .try_collect()
+            .map_err(|_| ParseError::new(line, start, "invalid or missing operator"))?;
+        match parsed.len() {
+            0 => Err(ParseError::new(line, start, "invalid or missing operator")),
+            1 => Ok(vec![parsed[0].clone(); count - 1]),
+            n if n == count - 1 => Ok(parsed),
+            _ if count <= 2 => Err(ParseError::new(line, start, "expected a single operator")),
+            _ => Err(ParseError::new(
+                line,
+                start,
+                &format!("expected 1 or {} operators", count - 1),
+            )),
         }
     }
 
-    fn parse_operations(data: &str) -> Vec<Operation> {
-        data.lines()
-            .last()
-            .map(|line| {
-                line.split_ascii_whitespace()
-                    .map(|x| Operation::from_str(x).unwrap())
-                    .collect()
-            })
-            .unwrap()
+    fn breakdown<T: Number>(&self) -> String {
+        // one line per problem, columns are 1-based like in parse errors
+        let problems = self.equations.iter().enumerate().map(|(index, equation)| {
+            let (start, end) = equation.span;
+            let result = match equation.calculate::<T>() {
+                Ok(result) => result.to_string(),
+                Err(error) => error.to_string(),
+            };
+            format!(
+                "#{} columns {}-{}: {} = {}",
+                index + 1,
+                start + 1,
+                end,
+                equation.expression,
+                result
+            )
+        });
+        let total = match self.total_sum::<T>() {
+            Ok(total) => total.to_string(),
+            Err(error) => error.to_string(),
+        };
+        problems.chain([format!("total = {}", total)]).join("\n")
     }
-    fn total_sum(&self) -> usize {
-        self.equations.iter().map(Equation::calculate).sum()
+
+    fn total_sum<T: Number>(&self) -> Result<T, CalculationError> {
+        self.equations
+            .iter()
+            .try_fold(T::from_usize(0), |total, equation| {
+                total
+                    .checked_add(&equation.calculate()?)
+                    .ok_or(CalculationError::Overflow)
+            })
     }
 }
 
-fn part1(equations: &Equations) -> usize {
+fn part1<T: Number>(equations: &Equations) -> Result<T, CalculationError> {
     equations.total_sum()
 }
 
-fn part2(equations: &Equations) -> usize {
+fn part2<T: Number>(equations: &Equations) -> Result<T, CalculationError> {
     equations.total_sum()
 }
+
+type Part<T> = fn(&Equations) -> Result<T, CalculationError>;
+
+fn report<T: Number>(equations: &Equations, part: Part<T>, verbose: bool) {
+    if verbose {
+        println!("{}", equations.breakdown::<T>());
+    }
+    match part(equations) {
+        Ok(total) => println!("{}", total),
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
 pub(crate) fn solve() {
     let contents = fs::read_to_string("6.txt").unwrap();
-    println!("{}", part1(&Equations::parse(&contents)));
-    println!("{}", part2(&Equations::parse_columns(&contents)));
+    let args: Vec<String> = env::args().skip(1).collect();
+    let verbose = args.iter().any(|arg| arg == "--breakdown");
+    let number = match args.iter().position(|arg| arg == "--number") {
+        Some(index) => args.get(index + 1).map_or("", String::as_str),
+        None => "usize",
+    };
+    if !["usize", "u128", "big"].contains(&number) {
+        return eprintln!("--number needs one of usize, u128 or big");
+    }
+    match (Equations::parse(&contents), number) {
+        (Ok(equations), "u128") => report(&equations, part1::<u128>, verbose),
+        (Ok(equations), "big") => report(&equations, part1::<BigUint>, verbose),
+        (Ok(equations), _) => report(&equations, part1::<usize>, verbose),
+        (Err(error), _) => eprintln!("{}", error),
+    }
+    match (Equations::parse_columns(&contents), number) {
+        (Ok(equations), "u128") => report(&equations, part2::<u128>, verbose),
+        (Ok(equations), "big") => report(&equations, part2::<BigUint>, verbose),
+        (Ok(equations), _) => report(&equations, part2::<usize>, verbose),
+        (Err(error), _) => eprintln!("{}", error),
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--layout") {
+        let Some(descriptor) = args.get(index + 1) else {
+            return eprintln!("--layout needs a layout, e.g. columns,right-to-left");
+        };
+        let layout = match Layout::new(descriptor) {
+            Ok(layout) => layout,
+            Err(error) => return eprintln!("{}", error),
+        };
+        match (Equations::parse_with(&contents, &layout), number) {
+            (Ok(equations), "u128") => report(&equations, Equations::total_sum::<u128>, verbose),
+            (Ok(equations), "big") => report(&equations, Equations::total_sum::<BigUint>, verbose),
+            (Ok(equations), _) => report(&equations, Equations::total_sum::<usize>, verbose),
+            (Err(error), _) => eprintln!("{}", error),
+        }
+    }
 }
@@ -1,35 +1,43 @@
+use crate::grid::Grid;
+use crate::parse::{ParseError, Token};
 use std::collections::{HashMap, HashSet};
 use std::fs;
 
+#[derive(Clone, PartialEq)]
+enum Cell {
+    Empty,
+    Start,
+    Splitter,
+}
+
 struct Manifold {
-    start: usize,
+    // columns are signed, beams may leave the grid on either side
+    start: i64,
     rows: usize,
-    splitters: HashSet<(usize, usize)>,
+    grid: Grid<Cell>,
 }
 
 impl Manifold {
-    fn new(data: &str) -> Self {
-        let mut splitters = HashSet::new();
-        let mut start = 0;
-        let mut rows = 0;
-        for (row, line) in data.lines().enumerate() {
-            for (col, c) in line.chars().enumerate() {
-                match (c) {
-                    'S' => start = col,
-                    '^' => {
-                        splitters.insert((row, col));
-                    }
-                    '.' => continue,
-                    _ => panic!(),
-                }
-            }
-            rows = row;
-        }
-        Manifold {
+    fn new(data: &str) -> Result<Self, ParseError> {
+        let grid = Grid::parse(Token::new(data), Cell::Empty, |c| match (c) {
+            'S' => Some(Cell::Start),
+            '^' => Some(Cell::Splitter),
+            '.' => Some(Cell::Empty),
+            _ => None,
+        })?;
+        let start = grid
+            .positions()
+            .find(|&position| grid[position] == Cell::Start)
+            .map_or(0, |(_, col)| col as i64);
+        Ok(Manifold {
             start,
-            rows,
-            splitters,
-        }
+            rows: grid.height().saturating_sub(1),
+            grid,
+        })
+    }
+
+    fn is_splitter(&self, (row, col): (usize, i64)) -> bool {
+        col >= 0 && self.grid.get(row, col as usize) == Some(&Cell::Splitter)
     }
 
     fn count_splits(&self) -> usize {
@@ -39,7 +47,8 @@ impl Manifold {
         for row in 1..self.rows {
             let mut new_beam_cols = HashSet::new();
             for col in beam_cols {
-                if self.splitters.contains(&(row, col)) {
+                if self.is_splitter((row, col)) {
+                    // beams which leave the grid sideways keep going and still count
                     new_beam_cols.insert(col - 1);
                     new_beam_cols.insert(col + 1);
                     splits += 1;
@@ -58,15 +67,15 @@ impl Manifold {
 
     fn count_timelines_recursively(
         &self,
-        (row, col): (usize, usize),
-        known: &mut HashMap<(usize, usize), usize>,
+        (row, col): (usize, i64),
+        known: &mut HashMap<(usize, i64), usize>,
     ) -> usize {
         if known.contains_key(&(row, col)) {
             known[&(row, col)]
         } else if row == self.rows {
             known.insert((row, col), 1);
             1
-        } else if self.splitters.contains(&(row, col)) {
+        } else if self.is_splitter((row, col)) {
             let left = self.count_timelines_recursively((row + 1, col - 1), known);
             let right = self.count_timelines_recursively((row + 1, col + 1), known);
             known.insert((row, col), left + right);
@@ -84,7 +93,10 @@ fn part2(manifold: &Manifold) -> usize {
 }
 pub(crate) fn solve() {
     let contents = fs::read_to_string("7.txt").unwrap();
-    let manifold = Manifold::new(&contents);
+    let manifold = match Manifold::new(&contents) {
+        Ok(manifold) => manifold,
+        Err(error) => return eprintln!("{}", error),
+    };
     println!("{}", part1(&manifold));
     println!("{}", part2(&manifold));
 }
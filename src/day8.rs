@@ -1,3 +1,4 @@
+use crate::parse::{ParseError, Token};
 use itertools::Itertools;
 use std::collections::HashMap;
 use std::fs;
@@ -22,17 +23,12 @@ struct Boxes {
 }
 
 impl Boxes {
-    fn new(data: &str) -> Self {
-        let boxes: Vec<Coord3d> = data
+    fn new(data: &str) -> Result<Self, ParseError> {
+        let boxes: Vec<Coord3d> = Token::new(data)
             .lines()
-            .map(|line| {
-                line.split(",")
-                    .map(|x| x.parse().unwrap())
-                    .collect_tuple()
-                    .unwrap()
-            })
-            .map(|(x, y, z)| Coord3d { x, y, z })
-            .collect();
+            .map(|line| line.tuple(","))
+            .map_ok(|(x, y, z)| Coord3d { x, y, z })
+            .try_collect()?;
         let distances: Vec<(f64, Coord3d, Coord3d)> = boxes
             .iter()
             .flat_map(|x| repeat(x).zip(boxes.iter()))
@@ -41,7 +37,7 @@ impl Boxes {
             .map(|(a, b)| (a.euclid_distance(b), a.clone(), b.clone()))
             .sorted_by(|(d1, _, _), (d2, _, _)| d1.partial_cmp(d2).unwrap())
             .collect();
-        Boxes { boxes, distances }
+        Ok(Boxes { boxes, distances })
     }
 
     fn connect(&self, limit: usize) -> (HashMap<Coord3d, Vec<Coord3d>>, (Coord3d, Coord3d)) {
@@ -103,7 +99,10 @@ fn part2(boxes: &Boxes) -> usize {
 }
 pub(crate) fn solve() {
     let contents = fs::read_to_string("8.txt").unwrap();
-    let boxes = Boxes::new(&contents);
+    let boxes = match Boxes::new(&contents) {
+        Ok(boxes) => boxes,
+        Err(error) => return eprintln!("{}", error),
+    };
     println!("{}", part1(&boxes));
     println!("{}", part2(&boxes));
 }
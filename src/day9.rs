@@ -1,3 +1,4 @@
+use crate::parse::{ParseError, Token};
 use geo::{Contains, LineString, Polygon, line_string};
 use itertools::Itertools;
 use std::fs;
@@ -8,14 +9,12 @@ struct Board {
 }
 
 impl Board {
-    fn new(data: &str) -> Self {
-        let corners = data
+    fn new(data: &str) -> Result<Self, ParseError> {
+        let corners = Token::new(data)
             .lines()
-            .map(|line| line.split(",").map(|coord| coord.parse().unwrap()))
-            .map(|x| x.collect_tuple())
-            .map(Option::unwrap)
-            .collect();
-        Self { corners }
+            .map(|line| line.pair(","))
+            .try_collect()?;
+        Ok(Self { corners })
     }
 
     fn rectangle_corners(&self) -> Vec<((usize, usize), (usize, usize))> {
@@ -70,7 +69,10 @@ fn part2(board: &Board) -> usize {
 
 pub(crate) fn solve() {
     let contents = fs::read_to_string("9.txt").unwrap();
-    let board = Board::new(&contents);
+    let board = match Board::new(&contents) {
+        Ok(board) => board,
+        Err(error) => return eprintln!("{}", error),
+    };
     println!("{}", part1(&board));
     println!("{}", part2(&board));
 }
@@ -0,0 +1,138 @@
+use crate::parse::{ParseError, Token};
+use itertools::Itertools;
+use std::fmt::{Display, Formatter};
+use std::ops::Index;
+
+#[derive(Clone)]
+pub(crate) struct Grid<T> {
+    width: usize,
+    height: usize,
+    // row-major
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub(crate) fn parse(
+        data: Token,
+        padding: T,
+        mut cell: impl FnMut(char) -> Option<T>,
+    ) -> Result<Self, ParseError>
+    where
+        T: Clone,
+    {
+        // shorter lines are padded with the given cell
+        let width = data
+            .lines()
+            .map(|line| line.text().chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut cells = vec![];
+        for line in data.lines() {
+            let mut length = 0;
+            for (offset, c) in line.text().char_indices() {
+                cells.push(cell(c).ok_or_else(|| line.at(offset).error("invalid cell"))?);
+                length += 1;
+            }
+            cells.extend((length..width).map(|_| padding.clone()));
+        }
+        Ok(Self {
+            width,
+            height: data.lines().count(),
+            cells,
+        })
+    }
+
+    pub(crate) fn from_fn(
+        height: usize,
+        width: usize,
+        mut cell: impl FnMut(usize, usize) -> T,
+    ) -> Self {
+        let cells = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (row, col)))
+            .map(|(row, col)| cell(row, col))
+            .collect();
+        Self {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> usize {
+        self.height
+    }
+
+    pub(crate) fn get(&self, row: usize, col: usize) -> Option<&T> {
+        if row < self.height && col < self.width {
+            Some(&self.cells[row * self.width + col])
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn rows(&self) -> impl Iterator<Item = &[T]> {
+        let width = self.width;
+        (0..self.height).map(move |row| &self.cells[row * width..(row + 1) * width])
+    }
+
+    pub(crate) fn positions(&self) -> impl Iterator<Item = (usize, usize)> {
+        let width = self.width;
+        (0..self.height).flat_map(move |row| (0..width).map(move |col| (row, col)))
+    }
+
+    pub(crate) fn neighbours<'a>(
+        &'a self,
+        (row, col): (usize, usize),
+        deltas: &'a [(i64, i64)],
+    ) -> impl Iterator<Item = ((usize, usize), &'a T)> {
+        deltas.iter().filter_map(move |(dr, dc)| {
+            let row = row.checked_add_signed(*dr as isize)?;
+            let col = col.checked_add_signed(*dc as isize)?;
+            Some(((row, col), self.get(row, col)?))
+        })
+    }
+
+    pub(crate) fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    pub(crate) fn transpose(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        Grid::from_fn(self.width, self.height, |row, col| self[(col, row)].clone())
+    }
+
+    pub(crate) fn rotate(&self) -> Grid<T>
+    where
+        T: Clone,
+    {
+        // clockwise, the first column read bottom-up becomes the first row
+        Grid::from_fn(self.width, self.height, |row, col| {
+            self[(self.height - 1 - col, row)].clone()
+        })
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        self.get(row, col).unwrap()
+    }
+}
+
+impl<T: Display> Display for Grid<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let text = self.rows().map(|row| row.iter().join("")).join("\n");
+        write!(f, "{}", text)
+    }
+}
@@ -10,6 +10,10 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+mod grid;
+mod parse;
+#[cfg(test)]
+mod random;
 
 fn main() {
     // day1::solve();
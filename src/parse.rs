@@ -0,0 +1,158 @@
+use itertools::Itertools;
+use itertools::traits::HomogeneousTuple;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, column: usize, message: &str) -> Self {
+        // positions are reported 1-based, like an editor would show them
+        ParseError {
+            line: line + 1,
+            column: column + 1,
+            message: message.to_string(),
+        }
+    }
+}
+
+// a slice of the input which remembers where it starts, 0-based
+#[derive(Clone, Copy)]
+pub(crate) struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Token<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Token {
+            text,
+            line: 0,
+            column: 0,
+        }
+    }
+
+    pub(crate) fn on_line(self, line: usize) -> Self {
+        // for input which is read line by line
+        Token { line, ..self }
+    }
+
+    pub(crate) fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub(crate) fn error(&self, message: &str) -> ParseError {
+        ParseError::new(self.line, self.column, message)
+    }
+
+    pub(crate) fn at(&self, offset: usize) -> Token<'a> {
+        let before = &self.text[..offset];
+        let column = match before.rfind('\n') {
+            Some(newline) => before[newline + 1..].chars().count(),
+            None => self.column + before.chars().count(),
+        };
+        Token {
+            text: &self.text[offset..],
+            line: self.line + before.matches('\n').count(),
+            column,
+        }
+    }
+
+    fn sub(&self, part: &'a str) -> Token<'a> {
+        // `part` has to be a slice of this token's text, its position follows from the pointers
+        let offset = part.as_ptr() as usize - self.text.as_ptr() as usize;
+        Token {
+            text: part,
+            ..self.at(offset)
+        }
+    }
+
+    pub(crate) fn lines(&self) -> impl Iterator<Item = Token<'a>> {
+        self.text.lines().map(|line| self.sub(line))
+    }
+
+    pub(crate) fn blocks(&self) -> impl Iterator<Item = Token<'a>> {
+        // blocks are separated by a blank line
+        self.text.split("\n\n").map(|block| self.sub(block))
+    }
+
+    pub(crate) fn split(&self, separator: &'a str) -> impl Iterator<Item = Token<'a>> {
+        self.text.split(separator).map(|part| self.sub(part))
+    }
+
+    pub(crate) fn split_once(&self, separator: &str) -> Result<(Token<'a>, Token<'a>), ParseError> {
+        let (left, right) = self
+            .text
+            .split_once(separator)
+            .ok_or_else(|| self.error(&format!("expected {:?}", separator)))?;
+        Ok((self.sub(left), self.sub(right)))
+    }
+
+    pub(crate) fn split_at(&self, mid: usize) -> Option<(Token<'a>, Token<'a>)> {
+        let (left, right) = self.text.split_at_checked(mid)?;
+        Some((self.sub(left), self.sub(right)))
+    }
+
+    pub(crate) fn trim(&self) -> Token<'a> {
+        self.sub(self.text.trim())
+    }
+
+    pub(crate) fn strip_prefix(&self, prefix: &str) -> Result<Token<'a>, ParseError> {
+        match self.text.strip_prefix(prefix) {
+            Some(rest) => Ok(self.sub(rest)),
+            None => Err(self.error(&format!("expected {:?}", prefix))),
+        }
+    }
+
+    pub(crate) fn strip_suffix(&self, suffix: &str) -> Result<Token<'a>, ParseError> {
+        match self.text.strip_suffix(suffix) {
+            Some(rest) => Ok(self.sub(rest)),
+            None => Err(self
+                .at(self.text.len())
+                .error(&format!("expected {:?}", suffix))),
+        }
+    }
+
+    pub(crate) fn parse<T: FromStr>(&self) -> Result<T, ParseError> {
+        T::from_str(self.text).map_err(|_| self.error(&format!("invalid value {:?}", self.text)))
+    }
+
+    pub(crate) fn pair<A: FromStr, B: FromStr>(
+        &self,
+        separator: &str,
+    ) -> Result<(A, B), ParseError> {
+        let (left, right) = self.split_once(separator)?;
+        Ok((left.parse()?, right.parse()?))
+    }
+
+    pub(crate) fn list<T: FromStr>(&self, separator: &'a str) -> Result<Vec<T>, ParseError> {
+        self.split(separator).map(|part| part.parse()).collect()
+    }
+
+    pub(crate) fn tuple<U>(&self, separator: &'a str) -> Result<U, ParseError>
+    where
+        U: HomogeneousTuple,
+        U::Item: FromStr,
+    {
+        self.list(separator)?
+            .into_iter()
+            .collect_tuple()
+            .ok_or_else(|| self.error(&format!("expected {} values", U::num_items())))
+    }
+}
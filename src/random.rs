@@ -0,0 +1,18 @@
+// xorshift, good enough to generate test inputs
+pub(crate) struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub(crate) fn new(seed: u64) -> Self {
+        // a zero state would stay zero forever
+        Random { state: seed.max(1) }
+    }
+
+    pub(crate) fn below(&mut self, limit: u64) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state % limit
+    }
+}